@@ -0,0 +1,210 @@
+// classical depth limited search next to mcts, so the two can be compared on the same positions
+// iterative deepening alpha-beta: we pick from our possible commands, the enemy answers with one of
+// possible_enemy_moves and picks whatever is worst for us, leaves are scored by the same evaluator
+
+use std::time::{Duration, Instant};
+
+use crate::{
+    config::SearchConfig,
+    enemy::possible_enemy_moves,
+    eval::LeafEvaluator,
+    mcts::{CombinedMoveCommand, RootMove, SearchReport},
+    playout,
+    state::{GeneralsGameState, MoveCommand, PlayerId},
+};
+
+// deepening stops here even if there is time left, nothing useful is that deep
+const MAX_DEPTH: u32 = 32;
+
+struct Searcher<'a> {
+    evaluator: &'a dyn LeafEvaluator,
+    player: PlayerId,
+    // None searches to the full depth however long that takes
+    deadline: Option<Instant>,
+    nodes: u32,
+}
+
+impl Searcher<'_> {
+    // counts the node, true once the deadline passed and the iteration has to be thrown away
+    fn out_of_time(&mut self) -> bool {
+        self.nodes += 1;
+        // checking the clock on every node costs more than it's worth
+        self.nodes.is_multiple_of(256)
+            && self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    fn is_leaf(&self, state: &GeneralsGameState, depth: u32) -> bool {
+        depth == 0 || state.get_winner().is_some() || state.reached_max_turns()
+    }
+
+    // our move, we take the best value
+    fn max_node(
+        &mut self,
+        state: &GeneralsGameState,
+        depth: u32,
+        mut alpha: f64,
+        beta: f64,
+    ) -> Option<(f64, Vec<CombinedMoveCommand>)> {
+        if self.out_of_time() {
+            return None;
+        }
+        if self.is_leaf(state, depth) {
+            return Some((self.evaluator.evaluate(state, self.player), vec![]));
+        }
+
+        let mut best = (f64::NEG_INFINITY, vec![]);
+        for m in ordered_moves(state) {
+            let (value, mut pv) =
+                self.min_node(&state.tick(&m).unwrap(), depth - 1, alpha, beta)?;
+            if value > best.0 {
+                pv.insert(0, CombinedMoveCommand::Friendly(m));
+                best = (value, pv);
+            }
+            alpha = alpha.max(value);
+            if alpha >= beta {
+                break;
+            }
+        }
+        Some(best)
+    }
+
+    // the enemy's answer, they take the worst value for us
+    fn min_node(
+        &mut self,
+        state: &GeneralsGameState,
+        depth: u32,
+        alpha: f64,
+        mut beta: f64,
+    ) -> Option<(f64, Vec<CombinedMoveCommand>)> {
+        if self.out_of_time() {
+            return None;
+        }
+        if self.is_leaf(state, depth) {
+            return Some((self.evaluator.evaluate(state, self.player), vec![]));
+        }
+
+        let enemy = 1 - self.player;
+        let mut best = (f64::INFINITY, vec![]);
+        for m in possible_enemy_moves(state, enemy) {
+            let child = m.apply_on_state(state, enemy);
+            let (value, mut pv) = self.max_node(&child, depth - 1, alpha, beta)?;
+            if value < best.0 {
+                pv.insert(0, CombinedMoveCommand::Enemy(m));
+                best = (value, pv);
+            }
+            beta = beta.min(value);
+            if alpha >= beta {
+                break;
+            }
+        }
+        Some(best)
+    }
+}
+
+// captures first, they decide most and make the cutoffs come early
+fn ordered_moves(state: &GeneralsGameState) -> Vec<MoveCommand> {
+    let mut moves = state.get_possible_commands();
+    moves.sort_by_cached_key(|m| std::cmp::Reverse(playout::move_prior(state, m)));
+    moves
+}
+
+// deepens up to alpha_beta_depth plies, or as deep as it gets if that is 0, a budget stops it
+// and the deepest finished iteration is kept, without one a fixed depth is always searched in full
+// the report's root moves carry their value as the score and the nodes below them as the visits,
+// moves that got cut off only have an upper bound for their value and are left out of it
+pub fn search(
+    state: &GeneralsGameState,
+    evaluator: &dyn LeafEvaluator,
+    config: &SearchConfig,
+    budget: Option<Duration>,
+) -> SearchReport {
+    // the same horizon as the mcts root
+    let mut state = state.clone();
+    state.max_turn = state.turn + config.horizon;
+
+    let max_depth = match config.alpha_beta_depth {
+        0 => {
+            assert!(
+                budget.is_some(),
+                "deepening without a fixed depth needs a budget"
+            );
+            MAX_DEPTH
+        }
+        depth => depth,
+    };
+    let mut searcher = Searcher {
+        evaluator,
+        player: state.player_id(),
+        deadline: budget.map(|budget| Instant::now() + budget),
+        nodes: 0,
+    };
+
+    let mut root_moves = ordered_moves(&state);
+    let mut completed: Option<(u32, Vec<RootMove>, Vec<CombinedMoveCommand>)> = None;
+    'deepening: for depth in 1..=max_depth {
+        let mut alpha = f64::NEG_INFINITY;
+        let mut scored = vec![];
+        let mut principal_variation = vec![];
+        for m in &root_moves {
+            let nodes = searcher.nodes;
+            let child = state.tick(m).unwrap();
+            let Some((value, mut pv)) = searcher.min_node(&child, depth - 1, alpha, f64::INFINITY)
+            else {
+                break 'deepening;
+            };
+            let exact = value > alpha;
+            if exact {
+                alpha = value;
+                pv.insert(0, CombinedMoveCommand::Friendly(m.clone()));
+                principal_variation = pv;
+            }
+            scored.push((
+                RootMove {
+                    command: m.clone(),
+                    visits: searcher.nodes - nodes,
+                    mean_score: value,
+                },
+                exact,
+            ));
+        }
+
+        // the next iteration looks at the best moves of this one first
+        scored.sort_by(|a, b| b.0.mean_score.total_cmp(&a.0.mean_score));
+        root_moves = scored.iter().map(|(m, _)| m.command.clone()).collect();
+        let exact = scored
+            .into_iter()
+            .filter_map(|(m, exact)| exact.then_some(m))
+            .collect();
+        completed = Some((depth, exact, principal_variation));
+    }
+
+    // the deepest finished iteration sorted its best move first
+    let best_move = root_moves[0].clone();
+    // not even depth 1 fit in the budget, any move beats none
+    let (depth, mut top_moves, principal_variation) = completed.unwrap_or_else(|| {
+        let first = root_moves[0].clone();
+        (
+            0,
+            vec![RootMove {
+                command: first.clone(),
+                visits: 0,
+                mean_score: f64::NAN,
+            }],
+            vec![CombinedMoveCommand::Friendly(first)],
+        )
+    });
+    top_moves.truncate(config.report_top_moves);
+
+    SearchReport {
+        best_move,
+        principal_variation,
+        top_moves,
+        rollouts: searcher.nodes,
+        root_visits: searcher.nodes,
+        // no tree is kept around
+        tree_size: 0,
+        depth: depth as usize,
+    }
+}
@@ -1,3 +1,5 @@
+use std::collections::VecDeque;
+
 use futures_util::stream::{SplitSink, SplitStream};
 use futures_util::{SinkExt, StreamExt};
 use native_tls::TlsConnector;
@@ -9,7 +11,7 @@ use tokio_tungstenite::{connect_async, tungstenite::WebSocket};
 use tokio_tungstenite::{connect_async_tls_with_config, Connector};
 
 use crate::constants::GIO_ENDPOINT;
-use crate::state::{MoveCommand, SerializedMoveCommand};
+use crate::state::{MoveCommand, SerializedMoveCommand, Tile};
 use crate::utils::fuck_socketio;
 
 #[derive(Deserialize, Debug)]
@@ -50,13 +52,46 @@ pub struct StateUpdate {
     pub turn: u64,
     pub generals: Vec<i64>,
     pub scores: Vec<PlayerScore>,
+
+    // id of the last move the server took off our queue, not every server sends it
+    #[serde(rename = "attackIndex", default)]
+    pub attack_index: Option<u64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct PendingMove {
+    pub id: u64,
+    pub cmd: SerializedMoveCommand,
+    // turn of the last update before the move was sent, it can't have been played before the next one
+    pub sent_turn: u64,
 }
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum MoveOutcome {
+    Executed,
+    // server took the move off the queue but nothing happened, e.g. the army was gone
+    Rejected,
+}
+
+impl PendingMove {
+    // a move executed if it drained the source tile, or the tile changed hands because of it
+    pub fn outcome(&self, from_before: &Tile, from_after: &Tile) -> MoveOutcome {
+        if from_before.owner != from_after.owner || from_after.population < from_before.population {
+            MoveOutcome::Executed
+        } else {
+            MoveOutcome::Rejected
+        }
+    }
+}
+
 pub struct GeneralsClient {
     tx: mpsc::UnboundedSender<String>,
 
     update_rx: mpsc::UnboundedReceiver<ServerUpdate>,
 
     move_id: u64,
+    pending: VecDeque<PendingMove>,
+    last_turn: u64,
 }
 
 pub enum LobbyType {
@@ -194,6 +229,8 @@ impl GeneralsClient {
             tx,
             update_rx,
             move_id: 1,
+            pending: VecDeque::new(),
+            last_turn: 0,
         };
 
         tokio::time::sleep(std::time::Duration::from_millis(500)).await;
@@ -240,14 +277,59 @@ impl GeneralsClient {
         self.tx.send(msg).unwrap();
     }
 
+    // queues a move on the server, reconcile follows it until the server takes it off the queue
     pub async fn send_cmd(&mut self, cmd: SerializedMoveCommand) {
-        self.send(cmd.to_json(self.move_id)).await;
+        let id = self.move_id;
+        self.send(cmd.to_json(id)).await;
+        self.pending.push_back(PendingMove {
+            id,
+            cmd,
+            sent_turn: self.last_turn,
+        });
         self.move_id += 1;
     }
 
+    // the server can only clear its whole queue, so that's all we cancel
     pub async fn clear_commands(&mut self) {
         self.send(Value::Array(vec![Value::String("clear_moves".to_owned())]))
             .await;
+        self.pending.clear();
+    }
+
+    // moves sent to the server which it has not taken off the queue yet, oldest first
+    pub fn pending_moves(&self) -> &VecDeque<PendingMove> {
+        &self.pending
+    }
+
+    // pops every pending move the server has acknowledged via attackIndex in this update
+    // without attackIndex the server is assumed to have played one move per turn since the last update,
+    // so the queue still drains and doesn't grow forever
+    pub fn reconcile(&mut self, update: &StateUpdate) -> Vec<PendingMove> {
+        let mut played = update.turn.saturating_sub(self.last_turn);
+        self.last_turn = update.turn;
+
+        let mut acknowledged = vec![];
+        while let Some(m) = self.pending.front() {
+            let done = match update.attack_index {
+                Some(attack_index) => m.id <= attack_index,
+                None => played > 0 && m.sent_turn < update.turn,
+            };
+            if !done {
+                break;
+            }
+            played = played.saturating_sub(1);
+            acknowledged.push(self.pending.pop_front().unwrap());
+        }
+
+        if !self.pending.is_empty() {
+            trace!(
+                "{} moves still pending after turn {}",
+                self.pending.len(),
+                update.turn
+            );
+        }
+
+        acknowledged
     }
 
     pub async fn wait_game_start(&mut self) -> GameStart {
@@ -297,3 +379,112 @@ impl GeneralsClient {
         Some(res)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::TileType;
+
+    fn client() -> (GeneralsClient, mpsc::UnboundedReceiver<String>) {
+        let (tx, sent) = mpsc::unbounded_channel();
+        let (_, update_rx) = mpsc::unbounded_channel();
+        let client = GeneralsClient {
+            tx,
+            update_rx,
+            move_id: 0,
+            pending: VecDeque::new(),
+            last_turn: 0,
+        };
+        (client, sent)
+    }
+
+    fn update(turn: u64, attack_index: Option<u64>) -> StateUpdate {
+        StateUpdate {
+            map_diff: vec![],
+            cities_diff: vec![],
+            turn,
+            generals: vec![],
+            scores: vec![],
+            attack_index,
+        }
+    }
+
+    async fn send(client: &mut GeneralsClient, count: u64) {
+        for i in 0..count {
+            client
+                .send_cmd(SerializedMoveCommand {
+                    from: i,
+                    to: i + 1,
+                    half: false,
+                })
+                .await;
+        }
+    }
+
+    fn ids(moves: &[PendingMove]) -> Vec<u64> {
+        moves.iter().map(|m| m.id).collect()
+    }
+
+    #[tokio::test]
+    async fn attack_index_pops_accepted_moves() {
+        let (mut client, _sent) = client();
+        send(&mut client, 3).await;
+
+        assert_eq!(ids(&client.reconcile(&update(1, Some(0)))), vec![0]);
+        assert_eq!(client.pending_moves().len(), 2);
+        assert_eq!(ids(&client.reconcile(&update(2, Some(2)))), vec![1, 2]);
+        assert!(client.pending_moves().is_empty());
+    }
+
+    // an update that arrives late carries an older attack index, it can't take back what was popped
+    // and ids of moves cleared since don't match anything that is queued now
+    #[tokio::test]
+    async fn stale_attack_index_pops_nothing() {
+        let (mut client, mut sent) = client();
+        send(&mut client, 2).await;
+        assert_eq!(ids(&client.reconcile(&update(1, Some(1)))), vec![0, 1]);
+        assert!(client.reconcile(&update(2, Some(0))).is_empty());
+
+        send(&mut client, 2).await;
+        client.clear_commands().await;
+        send(&mut client, 1).await;
+        assert!(client.reconcile(&update(3, Some(3))).is_empty());
+        assert_eq!(ids(&client.reconcile(&update(4, Some(4)))), vec![4]);
+
+        let messages: Vec<String> = std::iter::from_fn(|| sent.try_recv().ok()).collect();
+        assert_eq!(messages.len(), 6);
+        assert!(messages[4].contains("clear_moves"));
+    }
+
+    #[tokio::test]
+    async fn without_attack_index_one_move_drains_per_turn() {
+        let (mut client, _sent) = client();
+        send(&mut client, 3).await;
+
+        // sent after the update of turn 0, nothing of it could be played before turn 1
+        assert!(client.reconcile(&update(0, None)).is_empty());
+        assert_eq!(ids(&client.reconcile(&update(1, None))), vec![0]);
+        assert_eq!(ids(&client.reconcile(&update(3, None))), vec![1, 2]);
+        assert!(client.pending_moves().is_empty());
+    }
+
+    #[test]
+    fn acknowledged_moves_that_did_nothing_were_rejected() {
+        let m = PendingMove {
+            id: 0,
+            cmd: SerializedMoveCommand {
+                from: 0,
+                to: 1,
+                half: false,
+            },
+            sent_turn: 0,
+        };
+        let ours = |population| Tile::new(TileType::OwnedTile, population, Some(0));
+        let theirs = Tile::new(TileType::Enemy, 3, Some(1));
+
+        assert_eq!(m.outcome(&ours(5), &ours(1)), MoveOutcome::Executed);
+        assert_eq!(m.outcome(&ours(5), &theirs), MoveOutcome::Executed);
+        assert_eq!(m.outcome(&ours(5), &ours(5)), MoveOutcome::Rejected);
+        assert_eq!(m.outcome(&ours(1), &ours(2)), MoveOutcome::Rejected);
+    }
+}
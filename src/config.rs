@@ -0,0 +1,329 @@
+// runtime configuration, loaded from json files so we don't need to recompile to tune the bot
+
+use std::{path::Path, time::Duration};
+
+use anyhow::{ensure, Context, Result};
+use num_traits::float::FloatConst;
+
+use crate::{
+    constants::{MAX_TURNS, THINKING_TIME, THREAD_COUNT},
+    eval::EvaluatorKind,
+    playout::PlayoutKind,
+};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScoreWeights {
+    pub winner: f64,
+    pub army: f64,
+    pub fog: f64,
+    pub land: f64,
+    pub general_revealed: f64,
+    pub army_distance: f64,
+}
+
+impl Default for ScoreWeights {
+    fn default() -> Self {
+        Self {
+            winner: 50.,
+            army: 20.,
+            fog: 5.,
+            land: 9.,
+            general_revealed: 10.,
+            army_distance: 3.,
+        }
+    }
+}
+
+impl ScoreWeights {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        load_json(path.as_ref())
+    }
+
+    // reads the file pointed to by SCORE_WEIGHTS, or falls back to the defaults
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("SCORE_WEIGHTS") {
+            Ok(path) => Self::load(path),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EngineKind {
+    #[default]
+    Mcts,
+    // iterative deepening alpha-beta, see alphabeta.rs
+    AlphaBeta,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchConfig {
+    pub engine: EngineKind,
+    // plies (a move of ours or an enemy response) the alpha-beta engine searches at most,
+    // 0 deepens until the time is up, in a live game the time is up either way
+    pub alpha_beta_depth: u32,
+
+    // uct exploration constant, in units of the evaluator's score: hand_tuned scores go up to the sum of
+    // the positive score weights (84 by default), linear and neural predict a chance of winning from 0 to 1,
+    // so a constant tuned for one evaluator explores far more or far less with another
+    pub exploration: f64,
+    pub threads: usize,
+    pub thinking_time_ms: u64,
+    // how many turns past the root the search looks
+    pub horizon: u64,
+    // chance that a biased playout move is picked from the best moves instead of all of them
+    pub playout_bias_chance: f64,
+    // which fraction of the moves counts as the best moves
+    pub playout_bias_top_fraction: f64,
+    pub playout_policy: PlayoutKind,
+    // turns a playout runs before the leaf gets evaluated, 0 plays until the horizon
+    pub playout_depth: u64,
+    pub evaluator: EvaluatorKind,
+
+    // budget each move from the observed server tick instead of always using thinking_time_ms
+    pub adaptive_time: bool,
+    pub min_thinking_time_ms: u64,
+    pub max_thinking_time_ms: u64,
+    // kept free for the network on top of the observed jitter
+    pub latency_margin_ms: u64,
+    // share of the free time of a tick that goes to the search, in quiet and in tactical positions
+    pub time_fraction: f64,
+    pub tactical_time_fraction: f64,
+    // stop searching once the best root move has this share of the visits
+    pub decisive_visit_share: f64,
+    pub min_decisive_rollouts: u32,
+    // the opening book plays until this turn without searching, 50 is turn 25 on the game clock, 0 is off
+    pub opening_turns: u64,
+
+    // a strategic planner picks a goal every replan_every turns (or when it's done, or we're threatened),
+    // the search looks at moves towards it first and gets goal_weight for reaching it on top of the
+    // evaluator's score, as a fraction of the evaluator's score range so it means the same for
+    // every evaluator
+    pub planner: bool,
+    pub replan_every: u64,
+    pub goal_weight: f64,
+    // moves a gather onto the staging tile may take, see gather.rs
+    pub gather_turns: usize,
+
+    // root moves listed in the search report
+    pub report_top_moves: usize,
+
+    // memory for sharing statistics between transpositions, 0 turns the table off
+    pub transposition_table_mb: usize,
+
+    // information set search: every iteration plays on a fresh guess of what the fog hides
+    pub determinize: bool,
+
+    // only expand the most promising moves of a node at first, admitting more as it gets visited:
+    // a node may have widening_constant * visits^widening_exponent children
+    pub progressive_widening: bool,
+    pub widening_constant: f64,
+    pub widening_exponent: f64,
+
+    // every thread searches its own tree from the root instead of all sharing one, the root
+    // statistics of the trees are summed up for the decision
+    pub root_parallel: bool,
+
+    // tree nodes hold joint actions of both players resolved in the same tick, like the server does,
+    // instead of our move and the enemy response as separate levels
+    pub simultaneous_moves: bool,
+
+    // blend all-moves-as-first statistics into uct, they count for as much as the node's own
+    // statistics once it has rave_equivalence / 3 visits and fade out from there
+    pub rave: bool,
+    pub rave_equivalence: f64,
+
+    // seeds the random generator of every search worker, None draws from the os
+    pub seed: Option<u64>,
+    // search exactly this many iterations on a single thread instead of until the time is up, 0 is off
+    // together with a seed this makes decisions reproducible
+    pub iterations: u32,
+
+    // write the tree to log_dir/<replay id>/ after every decision, only its top and well visited part
+    pub export_tree: bool,
+    pub export_depth: usize,
+    pub export_min_visits: u32,
+    pub log_dir: String,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            engine: EngineKind::Mcts,
+            alpha_beta_depth: 0,
+            exploration: f64::SQRT_2(),
+            threads: THREAD_COUNT,
+            thinking_time_ms: THINKING_TIME,
+            horizon: MAX_TURNS,
+            playout_bias_chance: 0.4,
+            playout_bias_top_fraction: 0.1,
+            playout_policy: PlayoutKind::Biased,
+            playout_depth: 0,
+            evaluator: EvaluatorKind::HandTuned,
+            adaptive_time: true,
+            min_thinking_time_ms: 100,
+            max_thinking_time_ms: 450,
+            latency_margin_ms: 40,
+            time_fraction: 0.6,
+            tactical_time_fraction: 0.85,
+            decisive_visit_share: 0.8,
+            min_decisive_rollouts: 500,
+            opening_turns: 50,
+            planner: false,
+            replan_every: 10,
+            goal_weight: 0.06,
+            gather_turns: 20,
+            report_top_moves: 5,
+            transposition_table_mb: 64,
+            determinize: false,
+            progressive_widening: true,
+            widening_constant: 2.,
+            widening_exponent: 0.5,
+            root_parallel: false,
+            simultaneous_moves: false,
+            rave: false,
+            rave_equivalence: 1000.,
+            seed: None,
+            iterations: 0,
+            export_tree: false,
+            export_depth: 3,
+            export_min_visits: 20,
+            log_dir: "logs".to_string(),
+        }
+    }
+}
+
+impl SearchConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let config: Self = load_json(path.as_ref())?;
+        config
+            .validate()
+            .with_context(|| format!("invalid search config {}", path.as_ref().display()))?;
+        Ok(config)
+    }
+
+    // values that parse fine but the search can't work with
+    pub fn validate(&self) -> Result<()> {
+        ensure!(self.threads > 0, "threads must be at least 1");
+        ensure!(
+            self.exploration.is_finite() && self.exploration >= 0.,
+            "exploration must be a non-negative number"
+        );
+        ensure!(
+            self.thinking_time_ms > 0,
+            "thinking_time_ms must be positive"
+        );
+        ensure!(self.horizon > 0, "horizon must be at least 1");
+        ensure!(
+            (0. ..=1.).contains(&self.playout_bias_chance),
+            "playout_bias_chance must be between 0 and 1"
+        );
+        ensure!(
+            (0. ..=1.).contains(&self.playout_bias_top_fraction),
+            "playout_bias_top_fraction must be between 0 and 1"
+        );
+        ensure!(
+            self.min_thinking_time_ms <= self.max_thinking_time_ms,
+            "min_thinking_time_ms can't be more than max_thinking_time_ms"
+        );
+        ensure!(
+            (0. ..=1.).contains(&self.time_fraction)
+                && (0. ..=1.).contains(&self.tactical_time_fraction),
+            "time fractions must be between 0 and 1"
+        );
+        Ok(())
+    }
+
+    // reads the file pointed to by SEARCH_CONFIG, or falls back to the defaults
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("SEARCH_CONFIG") {
+            Ok(path) => Self::load(path),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    pub fn thinking_time(&self) -> Duration {
+        Duration::from_millis(self.thinking_time_ms)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SelfPlayConfig {
+    pub games: u64,
+    // seed of the first game, the others count up from it
+    pub seed: u64,
+    // ticks after which a game without a winner is stopped
+    pub max_turns: u64,
+    // ticks between logged positions
+    pub sample_every: u64,
+    pub policies: [PlayoutKind; 2],
+    // games stopped at max_turns count as won by the bigger army instead of being thrown away
+    pub label_unfinished: bool,
+
+    pub min_size: usize,
+    pub max_size: usize,
+    pub mountain_density: f64,
+    pub cities: usize,
+    pub city_army_min: u16,
+    pub city_army_max: u16,
+
+    pub epochs: usize,
+    pub learning_rate: f64,
+    pub l2: f64,
+
+    // positions the bench mode searches in both parallel modes
+    pub bench_positions: usize,
+
+    pub samples_path: String,
+    // loadable as the linear evaluator
+    pub output_path: String,
+}
+
+impl Default for SelfPlayConfig {
+    fn default() -> Self {
+        Self {
+            games: 200,
+            seed: 0,
+            max_turns: 1000,
+            sample_every: 10,
+            policies: [PlayoutKind::Heuristic, PlayoutKind::GreedyCapture],
+            label_unfinished: true,
+            min_size: 18,
+            max_size: 25,
+            mountain_density: 0.2,
+            cities: 10,
+            city_army_min: 40,
+            city_army_max: 50,
+            epochs: 2000,
+            learning_rate: 0.5,
+            l2: 0.001,
+            bench_positions: 50,
+            samples_path: "selfplay_samples.jsonl".to_string(),
+            output_path: "linear.json".to_string(),
+        }
+    }
+}
+
+impl SelfPlayConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        load_json(path.as_ref())
+    }
+
+    // reads the file pointed to by SELFPLAY_CONFIG, or falls back to the defaults
+    pub fn from_env() -> Result<Self> {
+        match std::env::var("SELFPLAY_CONFIG") {
+            Ok(path) => Self::load(path),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+}
+
+pub(crate) fn load_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read {}", path.display()))?;
+    serde_json::from_str(&contents).with_context(|| format!("failed to parse {}", path.display()))
+}
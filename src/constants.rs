@@ -4,7 +4,7 @@ pub const MAX_TURNS: u64 = 25;
 #[cfg(not(debug_assertions))]
 pub const MAX_TURNS: u64 = 125;
 
-// be careful with this, these threads are BLOCKING the event loop, they don't yield for performance reasons
+// search threads, they run outside the tokio runtime but still compete with it for cores
 #[cfg(not(debug_assertions))]
 pub const THREAD_COUNT: usize = 15;
 #[cfg(debug_assertions)]
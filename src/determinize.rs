@@ -0,0 +1,151 @@
+// fills in the fog with one plausible version of the truth
+// the search samples a new one every iteration, so its statistics average over what could be hidden
+
+use std::collections::VecDeque;
+
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    state::{GeneralLocation, GeneralsGameState, Location, PlayerId, Tile, TileType},
+    utils::{get_neighbors, manhattan_distance},
+};
+
+// generals never spawn closer than this to each other
+pub const MIN_GENERAL_DISTANCE: u64 = 9;
+
+// how many separate armies the enemy keeps in the fog besides the one on its general
+const HIDDEN_ARMY_STACKS: usize = 2;
+
+#[inline]
+fn is_hidden(state: &GeneralsGameState, location: Location) -> bool {
+    state.fog_mask[location.0][location.1] == 0
+}
+
+// fogged tiles the enemy general could be standing on
+pub fn general_candidates(state: &GeneralsGameState) -> Vec<Location> {
+    let own_general = state.get_own_general();
+    let general_distances = state.general_distances();
+
+    let mut candidates = vec![];
+    for x in 0..25 {
+        for y in 0..25 {
+            let tile = state.get_tile((x, y));
+            if tile.tile_type == TileType::AssumedEmpty
+                && is_hidden(state, (x, y))
+                && manhattan_distance((x, y), own_general) >= MIN_GENERAL_DISTANCE
+                && general_distances.get((x, y)).is_some()
+            {
+                candidates.push((x, y));
+            }
+        }
+    }
+    candidates
+}
+
+pub fn sample(state: &GeneralsGameState, rng: &mut (impl Rng + ?Sized)) -> GeneralsGameState {
+    let enemy: PlayerId = 1 - state.player_id();
+    let mut sampled = state.clone();
+
+    let general = match state.generals()[enemy as usize] {
+        GeneralLocation::Known(location) | GeneralLocation::Dead(location) => location,
+        GeneralLocation::Unknown => match general_candidates(state).choose(rng) {
+            Some(location) => {
+                sampled.set_tile(*location, Tile::new(TileType::EnemyGeneral, 1, Some(enemy)));
+                sampled.set_general(enemy, GeneralLocation::Known(*location));
+                *location
+            }
+            // nowhere to put it, nothing sensible to sample
+            None => return sampled,
+        },
+    };
+
+    // what we already know about the enemy, visible or remembered from earlier
+    let mut known_lands = 0;
+    let mut known_army = 0;
+    for x in 0..25 {
+        for y in 0..25 {
+            let tile = sampled.get_tile((x, y));
+            if tile.owner == Some(enemy) {
+                known_lands += 1;
+                known_army += tile.population as u32;
+            }
+        }
+    }
+
+    // grow the hidden territory out of the general, through tiles we can't see
+    let missing_lands = (state.lands[enemy as usize] as usize).saturating_sub(known_lands);
+    let mut hidden_tiles = vec![];
+    let mut queue = VecDeque::from([general]);
+    let mut seen = [[false; 25]; 25];
+    seen[general.0][general.1] = true;
+
+    while let Some(current) = queue.pop_front() {
+        if hidden_tiles.len() >= missing_lands {
+            break;
+        }
+        let mut neighbors = get_neighbors(current, 25, 25);
+        neighbors.shuffle(rng);
+
+        for n in neighbors {
+            if seen[n.0][n.1] {
+                continue;
+            }
+            seen[n.0][n.1] = true;
+
+            let tile = sampled.get_tile(n);
+            if tile.owner == Some(enemy) {
+                // walk through territory we already know about
+                queue.push_back(n);
+            } else if tile.tile_type == TileType::AssumedEmpty
+                && is_hidden(state, n)
+                && hidden_tiles.len() < missing_lands
+            {
+                sampled.set_tile(n, Tile::new(TileType::Enemy, 1, Some(enemy)));
+                hidden_tiles.push(n);
+                queue.push_back(n);
+            }
+        }
+    }
+
+    // whatever army we can't account for sits on the general and a few stacks in the fog
+    let unaccounted = (state.armies[enemy as usize] as u32)
+        .saturating_sub(known_army + hidden_tiles.len() as u32);
+    let on_general = unaccounted / 2;
+    let mut rest = unaccounted - on_general;
+
+    let general_tile = *sampled.get_tile(general);
+    if general_tile.owner == Some(enemy) && is_hidden(state, general) {
+        sampled.set_tile(
+            general,
+            Tile::new(
+                general_tile.tile_type,
+                general_tile.population + on_general as u16,
+                Some(enemy),
+            ),
+        );
+    } else {
+        rest += on_general;
+    }
+
+    let stacks: Vec<Location> = hidden_tiles
+        .choose_multiple(rng, HIDDEN_ARMY_STACKS)
+        .copied()
+        .collect();
+    for (i, location) in stacks.iter().copied().enumerate() {
+        // the last stack takes whatever is left
+        let stack = if i + 1 == stacks.len() {
+            rest
+        } else {
+            rng.gen_range(0..=rest)
+        };
+        rest -= stack;
+
+        let tile = *sampled.get_tile(location);
+        sampled.set_tile(
+            location,
+            Tile::new(tile.tile_type, tile.population + stack as u16, Some(enemy)),
+        );
+    }
+
+    sampled
+}
@@ -3,11 +3,13 @@
 use rand::seq::SliceRandom;
 
 use crate::{
+    path::UNREACHABLE,
+    rng,
     state::{GeneralsGameState, Location, MoveCommand, PlayerId, Tile},
-    utils::{get_neighbors, manhattan_distance},
+    utils::get_neighbors,
 };
 
-#[derive(Debug, Clone, Hash)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
 pub enum EnemyMove {
     Noop,
     ExpandLand,
@@ -15,6 +17,14 @@ pub enum EnemyMove {
 }
 
 impl EnemyMove {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            EnemyMove::Noop => "noop",
+            EnemyMove::ExpandLand => "expand_land",
+            EnemyMove::Invasion { .. } => "invasion",
+        }
+    }
+
     pub fn apply_on_state(
         &self,
         state: &GeneralsGameState,
@@ -95,8 +105,8 @@ pub fn possible_enemy_moves(state: &GeneralsGameState, player_id: PlayerId) -> V
                     < fictional_army_size.unwrap() / 2)
         {
             //prepend random of the possible_invade_spots, choose randomly
-            if let Some((x, y)) = possible_invade_spots.choose(&mut rand::thread_rng()) {
-                invader_armies.insert(0, (fictional_army_size.unwrap(), (*x, *y)));
+            if let Some((x, y)) = rng::with_rng(|rng| possible_invade_spots.choose(rng).copied()) {
+                invader_armies.insert(0, (fictional_army_size.unwrap(), (x, y)));
             }
         }
 
@@ -124,7 +134,8 @@ pub fn possible_enemy_moves(state: &GeneralsGameState, player_id: PlayerId) -> V
             }
 
             if !in_movements.is_empty() {
-                // remove the move which 'to' is furthest from our general
+                // remove the move which 'to' is furthest from our general, walking around mountains
+                let general_distances = state.general_distances();
                 let mut max_distance_ind = 0;
                 let mut max_distance = 0;
                 for (i, m) in in_movements.iter().enumerate() {
@@ -132,7 +143,7 @@ pub fn possible_enemy_moves(state: &GeneralsGameState, player_id: PlayerId) -> V
                         EnemyMove::Invasion { from: _, to } => *to,
                         _ => panic!("not an invasion"),
                     };
-                    let distance = manhattan_distance(to, state.get_own_general());
+                    let distance = general_distances.get(to).unwrap_or(UNREACHABLE);
                     if distance > max_distance {
                         max_distance = distance;
                         max_distance_ind = i;
@@ -0,0 +1,278 @@
+// leaf evaluators for the search, picked at startup through the search config
+
+use std::{collections::HashMap, fmt::Debug, path::Path, sync::Arc};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    config::{load_json, ScoreWeights},
+    state::{GeneralsGameState, PlayerId, TileType},
+};
+
+#[derive(Clone, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EvaluatorKind {
+    // get_score with the weights from SCORE_WEIGHTS
+    #[default]
+    HandTuned,
+    Linear {
+        path: String,
+    },
+    Neural {
+        path: String,
+    },
+}
+
+pub trait LeafEvaluator: Debug + Send + Sync {
+    fn evaluate(&self, state: &GeneralsGameState, turn: PlayerId) -> f64;
+
+    // how far apart the scores of a lost and a won position are, anything added to the score
+    // is given as a fraction of it, the learned evaluators predict a chance of winning
+    fn score_range(&self) -> f64 {
+        1.
+    }
+}
+
+pub fn from_config(
+    kind: &EvaluatorKind,
+    weights: Arc<ScoreWeights>,
+) -> Result<Arc<dyn LeafEvaluator>> {
+    Ok(match kind {
+        EvaluatorKind::HandTuned => Arc::new(HandTuned { weights }),
+        EvaluatorKind::Linear { path } => Arc::new(Linear::load(path)?),
+        EvaluatorKind::Neural { path } => Arc::new(NeuralNet::load(path)?),
+    })
+}
+
+#[inline]
+fn sigmoid(x: f64) -> f64 {
+    1. / (1. + (-x).exp())
+}
+
+// learned evaluators predict the chance of winning, so finished games are simply 1 or 0
+fn decided(state: &GeneralsGameState, turn: PlayerId) -> Option<f64> {
+    state
+        .get_winner()
+        .map(|winner| if winner == turn { 1. } else { 0. })
+}
+
+pub const FEATURE_NAMES: [&str; 7] = [
+    "winner",
+    "army",
+    "fog",
+    "land",
+    "general_revealed",
+    "army_distance",
+    "cities",
+];
+pub const FEATURE_COUNT: usize = FEATURE_NAMES.len();
+
+// the unweighted terms of get_score, plus our share of the cities
+pub fn features(state: &GeneralsGameState, turn: PlayerId) -> [f64; FEATURE_COUNT] {
+    let unit = ScoreWeights {
+        winner: 1.,
+        army: 1.,
+        fog: 1.,
+        land: 1.,
+        general_revealed: 1.,
+        army_distance: 1.,
+    };
+    let terms = state.get_score_breakdown(&turn, &unit);
+
+    let all_cities: u16 = state.city_count.iter().sum();
+    let cities = state.city_count[turn as usize] as f64 / all_cities.max(1) as f64;
+
+    [
+        terms.winner,
+        terms.army,
+        terms.fog,
+        terms.land,
+        terms.general_revealed,
+        terms.army_distance,
+        cities,
+    ]
+}
+
+#[derive(Debug)]
+pub struct HandTuned {
+    pub weights: Arc<ScoreWeights>,
+}
+impl LeafEvaluator for HandTuned {
+    fn evaluate(&self, state: &GeneralsGameState, turn: PlayerId) -> f64 {
+        state.get_score(&turn, &self.weights)
+    }
+
+    // the positive terms all count from 0 to their weight
+    fn score_range(&self) -> f64 {
+        self.weights.winner + self.weights.army + self.weights.fog + self.weights.land
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct LinearWeights {
+    pub bias: f64,
+    // keyed by FEATURE_NAMES, missing features weigh 0
+    pub weights: HashMap<String, f64>,
+}
+
+// logistic model over the features
+#[derive(Debug)]
+pub struct Linear {
+    bias: f64,
+    weights: [f64; FEATURE_COUNT],
+}
+impl Linear {
+    pub fn new(file: &LinearWeights) -> Result<Self> {
+        if let Some(unknown) = file
+            .weights
+            .keys()
+            .find(|name| !FEATURE_NAMES.contains(&name.as_str()))
+        {
+            bail!("unknown feature {} in linear weights", unknown);
+        }
+
+        let mut weights = [0.; FEATURE_COUNT];
+        for (i, name) in FEATURE_NAMES.iter().enumerate() {
+            weights[i] = file.weights.get(*name).copied().unwrap_or_default();
+        }
+
+        Ok(Self {
+            bias: file.bias,
+            weights,
+        })
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::new(&load_json(path.as_ref()).context("failed to load linear evaluator weights")?)
+    }
+}
+impl LeafEvaluator for Linear {
+    fn evaluate(&self, state: &GeneralsGameState, turn: PlayerId) -> f64 {
+        if let Some(result) = decided(state, turn) {
+            return result;
+        }
+
+        let features = features(state, turn);
+        let z = self.bias
+            + features
+                .iter()
+                .zip(self.weights.iter())
+                .map(|(f, w)| f * w)
+                .sum::<f64>();
+        sigmoid(z)
+    }
+}
+
+const PLANE_COUNT: usize = 7;
+pub const NETWORK_INPUTS: usize = PLANE_COUNT * 25 * 25 + FEATURE_COUNT;
+
+// one value per tile and plane, seen from `turn`, followed by the linear features
+pub fn tile_planes(state: &GeneralsGameState, turn: PlayerId) -> Vec<f64> {
+    let mut input = vec![0.; NETWORK_INPUTS];
+
+    for x in 0..25 {
+        for y in 0..25 {
+            let tile = state.get_tile((x, y));
+            let offset = (x * 25 + y) * PLANE_COUNT;
+            let army = (tile.population as f64).ln_1p() / 5.;
+
+            match tile.owner {
+                Some(owner) if owner == turn => input[offset] = army,
+                Some(_) => input[offset + 1] = army,
+                None if matches!(
+                    tile.tile_type,
+                    TileType::VisibleNeutralCity | TileType::HiddenNeutralCity
+                ) =>
+                {
+                    input[offset + 2] = army
+                }
+                None => {}
+            }
+
+            if !tile.tile_type.occupiable() {
+                input[offset + 3] = 1.;
+            }
+            if state.fog_mask[x][y] > 0 {
+                input[offset + 4] = 1.;
+            }
+
+            let (ours, theirs) = match tile.tile_type {
+                TileType::OwnedGeneral => (2., 0.),
+                TileType::OwnedCity => (1., 0.),
+                TileType::EnemyGeneral => (0., 2.),
+                TileType::EnemyCity => (0., 1.),
+                _ => (0., 0.),
+            };
+            // tile types are from our point of view, flip them when scoring for the enemy
+            let (ours, theirs) = if turn == state.player_id() {
+                (ours, theirs)
+            } else {
+                (theirs, ours)
+            };
+            input[offset + 5] = ours;
+            input[offset + 6] = theirs;
+        }
+    }
+
+    input[PLANE_COUNT * 25 * 25..].copy_from_slice(&features(state, turn));
+    input
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DenseLayer {
+    // one row of inputs per output
+    pub weights: Vec<Vec<f64>>,
+    pub bias: Vec<f64>,
+}
+impl DenseLayer {
+    fn forward(&self, input: &[f64]) -> Vec<f64> {
+        self.weights
+            .iter()
+            .zip(self.bias.iter())
+            .map(|(row, bias)| bias + row.iter().zip(input).map(|(w, x)| w * x).sum::<f64>())
+            .collect()
+    }
+}
+
+// small fully connected network, relu between layers and a sigmoid on the single output
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NeuralNet {
+    pub layers: Vec<DenseLayer>,
+}
+impl NeuralNet {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let net: Self =
+            load_json(path.as_ref()).context("failed to load neural evaluator weights")?;
+
+        let mut inputs = NETWORK_INPUTS;
+        for (i, layer) in net.layers.iter().enumerate() {
+            if layer.weights.len() != layer.bias.len()
+                || layer.weights.iter().any(|row| row.len() != inputs)
+            {
+                bail!("layer {} doesn't take {} inputs", i, inputs);
+            }
+            inputs = layer.bias.len();
+        }
+        if inputs != 1 {
+            bail!("network has {} outputs instead of 1", inputs);
+        }
+
+        Ok(net)
+    }
+}
+impl LeafEvaluator for NeuralNet {
+    fn evaluate(&self, state: &GeneralsGameState, turn: PlayerId) -> f64 {
+        if let Some(result) = decided(state, turn) {
+            return result;
+        }
+
+        let mut activations = tile_planes(state, turn);
+        for (i, layer) in self.layers.iter().enumerate() {
+            activations = layer.forward(&activations);
+            if i + 1 < self.layers.len() {
+                activations.iter_mut().for_each(|a| *a = a.max(0.));
+            }
+        }
+        sigmoid(activations[0])
+    }
+}
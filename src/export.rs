@@ -0,0 +1,80 @@
+// dumps the top of the search tree after a decision, so games can be picked apart afterwards
+// json for scripts, graphviz dot for looking at it: `dot -Tsvg turn_0042.dot > turn_0042.svg`
+
+use std::{fmt::Write as _, path::Path};
+
+use anyhow::{Context, Result};
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ExportedNode {
+    // the move leading to this node, None for the root
+    #[serde(rename = "move")]
+    pub move_: Option<String>,
+    // noop, expand_land or invasion for enemy and joint nodes
+    pub enemy_move: Option<&'static str>,
+    pub visits: u32,
+    pub mean_reward: f64,
+    // what selection saw for this node from its parent
+    pub uct: Option<f64>,
+    pub children: Vec<ExportedNode>,
+}
+
+impl ExportedNode {
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph search {\n    node [fontname=\"monospace\"];\n");
+        let mut next_id = 0;
+        self.write_dot(&mut dot, &mut next_id, None);
+        dot.push_str("}\n");
+        dot
+    }
+
+    fn write_dot(&self, dot: &mut String, next_id: &mut usize, parent: Option<usize>) {
+        let id = *next_id;
+        *next_id += 1;
+
+        let title = match (&self.move_, self.enemy_move) {
+            (None, _) => "root".to_string(),
+            (Some(m), Some(kind)) => format!("{} {}", kind, m),
+            (Some(m), None) => m.clone(),
+        };
+        let uct = self.uct.map(|u| format!(" u={:.3}", u)).unwrap_or_default();
+        // friendly moves are boxes, enemy moves ellipses
+        let shape = if self.enemy_move.is_some() {
+            "ellipse"
+        } else {
+            "box"
+        };
+        let _ = writeln!(
+            dot,
+            "    n{} [shape={}, label=\"{}\\nn={} r={:.3}{}\"];",
+            id,
+            shape,
+            title.replace('"', "'"),
+            self.visits,
+            self.mean_reward,
+            uct
+        );
+        if let Some(parent) = parent {
+            let _ = writeln!(dot, "    n{} -> n{};", parent, id);
+        }
+
+        for child in &self.children {
+            child.write_dot(dot, next_id, Some(id));
+        }
+    }
+
+    // writes turn_<turn>.json and turn_<turn>.dot into dir
+    pub fn write(&self, dir: &Path, turn: u64) -> Result<()> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create {}", dir.display()))?;
+
+        let json = dir.join(format!("turn_{:04}.json", turn));
+        std::fs::write(&json, serde_json::to_string(self)?)
+            .with_context(|| format!("failed to write {}", json.display()))?;
+
+        let dot = dir.join(format!("turn_{:04}.dot", turn));
+        std::fs::write(&dot, self.to_dot())
+            .with_context(|| format!("failed to write {}", dot.display()))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+// gathering scattered armies onto one tile, which single-move search rarely finds on its own:
+// pick the tree of our tiles around the target that brings the most army within the move budget,
+// then move every tile of it onto its parent, leaves first, so everything ends up on the target
+
+use std::collections::VecDeque;
+
+use crate::{
+    state::{GeneralsGameState, Location, MoveCommand},
+    utils::get_neighbors,
+};
+
+#[derive(Clone, Debug)]
+pub struct Gather {
+    pub target: Location,
+    // in the order they have to be played, one per turn
+    pub moves: Vec<MoveCommand>,
+    // army arriving on the target, not counting what grows while the moves are played
+    pub army: u32,
+}
+
+// a whole gather is queued at once and played out before the search takes over again, the turn
+// a gather ends is always searched so gathers don't follow each other without the search looking
+#[derive(Debug, Default)]
+pub struct Gathering {
+    // moves of a gather are still queued on the server
+    active: bool,
+}
+
+#[derive(Debug)]
+pub enum GatherStep {
+    // a gather is still playing out, nothing to add
+    Wait,
+    // queue the moves of this gather
+    Start(Gather),
+    // the gather is over, whatever it still has queued goes and the search has this turn
+    Stop,
+    // the search has this turn
+    Search,
+}
+
+impl Gathering {
+    // staging is where the planner wants army gathered, None for any other goal
+    pub fn step(
+        &mut self,
+        state: &GeneralsGameState,
+        staging: Option<Location>,
+        queue_empty: bool,
+        max_moves: usize,
+    ) -> GatherStep {
+        if self.active {
+            if staging.is_some() && !queue_empty {
+                return GatherStep::Wait;
+            }
+            self.active = false;
+            return GatherStep::Stop;
+        }
+
+        let Some(staging) = staging else {
+            return GatherStep::Search;
+        };
+        let gather = plan(state, staging, max_moves);
+        // not worth it unless it at least doubles what is already there
+        if gather.moves.is_empty() || gather.army < state.get_tile(staging).population as u32 {
+            return GatherStep::Search;
+        }
+        self.active = true;
+        GatherStep::Start(gather)
+    }
+}
+
+// the tree is taken from the shortest paths over our land towards target, on that tree the best
+// subtree for every move count is found bottom up, like a knapsack over the children of each tile
+pub fn plan(state: &GeneralsGameState, target: Location, max_moves: usize) -> Gather {
+    let me = Some(state.player_id());
+    let mut gather = Gather {
+        target,
+        moves: vec![],
+        army: 0,
+    };
+    if state.get_tile(target).owner != me || max_moves == 0 {
+        return gather;
+    }
+
+    // shortest path tree over our land, in bfs order so children come after their parents
+    let mut order = vec![target];
+    let mut parent = vec![None];
+    let mut depth = vec![0];
+    let mut index = [[usize::MAX; 25]; 25];
+    index[target.0][target.1] = 0;
+    let mut queue = VecDeque::from([0]);
+    while let Some(i) = queue.pop_front() {
+        for n in get_neighbors(order[i], 25, 25) {
+            if index[n.0][n.1] == usize::MAX && state.get_tile(n).owner == me {
+                index[n.0][n.1] = order.len();
+                queue.push_back(order.len());
+                order.push(n);
+                parent.push(Some(i));
+                depth.push(depth[i] + 1);
+            }
+        }
+    }
+    let mut children = vec![vec![]; order.len()];
+    for (i, p) in parent.iter().enumerate() {
+        if let Some(p) = p {
+            children[*p].push(i);
+        }
+    }
+
+    // best[i][k]: most army tile i and the tiles below it bring with at most k moves below i
+    // taken[i][c][k]: moves spent below child c of i when i had k moves for its first c + 1 children
+    let budget = max_moves.min(order.len() - 1);
+    let mut best: Vec<Vec<u32>> = vec![vec![]; order.len()];
+    let mut taken: Vec<Vec<Vec<Option<usize>>>> = vec![vec![]; order.len()];
+    for i in (0..order.len()).rev() {
+        // the target's army is already there, everything else leaves 1 behind
+        let own = match i {
+            0 => 0,
+            _ => state.get_tile(order[i]).population.saturating_sub(1) as u32,
+        };
+        let mut values = vec![own; budget + 1];
+        for &c in &children[i] {
+            let mut merged = values.clone();
+            let mut choice = vec![None; budget + 1];
+            for k in 0..=budget {
+                // one move brings the child over, the rest is spent below it
+                for below in 0..k {
+                    let value = values[k - 1 - below] + best[c][below];
+                    if value > merged[k] {
+                        merged[k] = value;
+                        choice[k] = Some(below);
+                    }
+                }
+            }
+            values = merged;
+            taken[i].push(choice);
+        }
+        best[i] = values;
+    }
+
+    // walk the choices back down to find which tiles take part
+    let mut included = vec![];
+    let mut stack = vec![(0, budget)];
+    while let Some((i, mut k)) = stack.pop() {
+        for (c, &child) in children[i].iter().enumerate().rev() {
+            if let Some(below) = taken[i][c][k] {
+                included.push(child);
+                stack.push((child, below));
+                k -= 1 + below;
+            }
+        }
+    }
+
+    // deepest first, every tile moves once everything below it has arrived
+    included.sort_by_key(|i| std::cmp::Reverse(depth[*i]));
+    gather.moves = included
+        .into_iter()
+        .map(|i| MoveCommand {
+            from: order[i],
+            to: order[parent[i].unwrap()],
+            half: false,
+        })
+        .collect();
+    gather.army = best[0][budget];
+    gather
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{GameState, Tile, TileType};
+
+    // our land: a chain (5, 5) <- (5, 6) <- (5, 7) and a branch (5, 5) <- (6, 5)
+    fn board() -> GeneralsGameState {
+        let mut state: GeneralsGameState = GameState::new(0, (0, 0));
+        for (location, population) in [((5, 5), 1), ((5, 6), 4), ((5, 7), 10), ((6, 5), 3)] {
+            state.set_tile(
+                location,
+                Tile::new(TileType::OwnedTile, population, Some(0)),
+            );
+        }
+        state
+    }
+
+    fn moves(gather: &Gather) -> Vec<(Location, Location)> {
+        gather.moves.iter().map(|m| (m.from, m.to)).collect()
+    }
+
+    #[test]
+    fn picks_the_subtree_with_the_most_army() {
+        let state = board();
+
+        // two moves bring the chain, 3 + 9, the branch would only add 2
+        let gather = plan(&state, (5, 5), 2);
+        assert_eq!(gather.army, 12);
+        assert_eq!(moves(&gather), vec![((5, 7), (5, 6)), ((5, 6), (5, 5))]);
+
+        // one move only reaches a neighbour of the target
+        let gather = plan(&state, (5, 5), 1);
+        assert_eq!(gather.army, 3);
+        assert_eq!(moves(&gather), vec![((5, 6), (5, 5))]);
+
+        // everything, deepest first
+        let gather = plan(&state, (5, 5), 5);
+        assert_eq!(gather.army, 14);
+        assert_eq!(gather.moves.len(), 3);
+        assert_eq!(gather.moves[0].from, (5, 7));
+
+        assert!(plan(&state, (9, 9), 5).moves.is_empty());
+        assert!(plan(&state, (5, 5), 0).moves.is_empty());
+    }
+
+    #[test]
+    fn searches_a_turn_between_gathers() {
+        let state = board();
+        let mut gathering = Gathering::default();
+
+        assert!(matches!(
+            gathering.step(&state, Some((5, 5)), true, 2),
+            GatherStep::Start(_)
+        ));
+        assert!(matches!(
+            gathering.step(&state, Some((5, 5)), false, 2),
+            GatherStep::Wait
+        ));
+        // played out, another gather would still be worth it but the search goes first
+        assert!(matches!(
+            gathering.step(&state, Some((5, 5)), true, 2),
+            GatherStep::Stop
+        ));
+        assert!(matches!(
+            gathering.step(&state, Some((5, 5)), true, 2),
+            GatherStep::Start(_)
+        ));
+        // the planner moved on, the rest of the gather goes
+        assert!(matches!(
+            gathering.step(&state, None, false, 2),
+            GatherStep::Stop
+        ));
+        assert!(matches!(
+            gathering.step(&state, None, true, 2),
+            GatherStep::Search
+        ));
+    }
+}
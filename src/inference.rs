@@ -0,0 +1,273 @@
+// where the enemy general probably is while we haven't seen it
+// every fogged tile it could have spawned on starts out equally likely, tiles we have looked at are
+// ruled out for good, and the enemy land we know of and how much land the enemy has weigh the rest:
+// its territory grew out of the general, so the general sits behind it and has room for all of it,
+// and land we saw early on, while the territory was still small, can't be far from it
+
+use std::collections::VecDeque;
+
+use crate::{
+    determinize::MIN_GENERAL_DISTANCE,
+    state::{GeneralLocation, GeneralsGameState, Location, PlayerId, TileType},
+    utils::{get_neighbors, manhattan_distance},
+};
+
+#[derive(Clone, Debug)]
+pub struct GeneralBelief {
+    // tiles we have seen at some point, the general wasn't on them or we would know
+    seen: [[bool; 25]; 25],
+    // the enemy land count when we first saw a tile owned by the enemy, 0 if we never did
+    enemy_lands_when_seen: [[u16; 25]; 25],
+    probabilities: [[f64; 25]; 25],
+}
+
+impl Default for GeneralBelief {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GeneralBelief {
+    pub fn new() -> Self {
+        Self {
+            seen: [[false; 25]; 25],
+            enemy_lands_when_seen: [[0; 25]; 25],
+            probabilities: [[0.; 25]; 25],
+        }
+    }
+
+    pub fn probability(&self, location: Location) -> f64 {
+        self.probabilities[location.0][location.1]
+    }
+
+    // the k most likely tiles, most likely first
+    pub fn top(&self, k: usize) -> Vec<(Location, f64)> {
+        let mut candidates: Vec<(Location, f64)> = (0..25)
+            .flat_map(|x| (0..25).map(move |y| (x, y)))
+            .map(|l| (l, self.probability(l)))
+            .filter(|(_, p)| *p > 0.)
+            .collect();
+        candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+        candidates.truncate(k);
+        candidates
+    }
+
+    // call with every new state of the game, the tiles seen in it are remembered
+    pub fn update(&mut self, state: &GeneralsGameState) {
+        let enemy = 1 - state.player_id() as usize;
+        let lands = state.lands[enemy].max(1);
+        for x in 0..25 {
+            for y in 0..25 {
+                if state.fog_mask[x][y] != 0 {
+                    self.seen[x][y] = true;
+                }
+                if self.enemy_lands_when_seen[x][y] == 0
+                    && state.get_tile((x, y)).owner == Some(enemy as PlayerId)
+                {
+                    self.enemy_lands_when_seen[x][y] = lands;
+                }
+            }
+        }
+
+        self.probabilities = [[0.; 25]; 25];
+        match state.generals()[enemy] {
+            GeneralLocation::Known(l) | GeneralLocation::Dead(l) => {
+                self.probabilities[l.0][l.1] = 1.;
+                return;
+            }
+            GeneralLocation::Unknown => {}
+        }
+
+        // spawn rules and what we have seen
+        let own_general = state.get_own_general();
+        let general_distances = state.general_distances();
+        let candidates: Vec<Location> = (0..25)
+            .flat_map(|x| (0..25).map(move |y| (x, y)))
+            .filter(|l| {
+                !self.seen[l.0][l.1]
+                    && state.get_tile(*l).tile_type == TileType::AssumedEmpty
+                    && manhattan_distance(*l, own_general) >= MIN_GENERAL_DISTANCE
+                    && general_distances.get(*l).is_some()
+            })
+            .collect();
+
+        let lands = lands as usize;
+        let knows_enemy_land = state
+            .tiles()
+            .iter()
+            .flatten()
+            .any(|tile| tile.owner == Some(enemy as PlayerId));
+        // the enemy land we saw while the enemy had the least of it, when a tile is taken it is
+        // connected to the general through fewer tiles than the enemy had then
+        let earliest = (0..25)
+            .flat_map(|x| (0..25).map(move |y| (x, y)))
+            .filter(|l| state.get_tile(*l).owner == Some(enemy as PlayerId))
+            .map(|l| (l, self.enemy_lands_when_seen[l.0][l.1] as usize))
+            .min_by_key(|(_, lands)| *lands);
+        let weights: Vec<f64> = candidates
+            .iter()
+            .map(|c| territory_likelihood(state, *c, lands, knows_enemy_land, earliest))
+            .collect();
+        // what we know of the enemy contradicts itself, e.g. its land got cut in two,
+        // better to fall back on the spawn rules than to know nothing
+        let total: f64 = weights.iter().sum();
+        let (weights, total) = if total > 0. {
+            (weights, total)
+        } else {
+            (vec![1.; candidates.len()], candidates.len() as f64)
+        };
+        for (c, w) in candidates.iter().zip(weights) {
+            self.probabilities[c.0][c.1] = w / total;
+        }
+    }
+}
+
+// the army on the enemy general at location, guessed while the tile is fogged: the general makes
+// one army every other turn, and the enemy army that isn't holding its land is all it can have
+pub fn general_army(state: &GeneralsGameState, location: Location) -> u32 {
+    if state.fog_mask[location.0][location.1] != 0 {
+        return state.get_tile(location).population as u32;
+    }
+    let enemy = 1 - state.player_id() as usize;
+    let spare = (state.armies[enemy] as u32).saturating_sub(state.lands[enemy] as u32);
+    (state.turn as u32 / 2).min(spare) + 1
+}
+
+// tiles the enemy could own without us knowing, besides the ones we know it does
+fn could_be_enemy(state: &GeneralsGameState, location: Location) -> bool {
+    let tile = state.get_tile(location);
+    tile.owner == Some(1 - state.player_id())
+        || (state.fog_mask[location.0][location.1] == 0
+            && tile.owner.is_none()
+            && tile.tile_type == TileType::AssumedEmpty)
+}
+
+// how well a general on candidate explains the enemy territory, 0 if it can't
+fn territory_likelihood(
+    state: &GeneralsGameState,
+    candidate: Location,
+    lands: usize,
+    knows_enemy_land: bool,
+    earliest: Option<(Location, usize)>,
+) -> f64 {
+    let enemy = Some(1 - state.player_id());
+
+    // walk out of the candidate the way its territory would have grown, through the fog and
+    // the enemy land we know of, until there is room for all of its land
+    let mut distance = [[u32::MAX; 25]; 25];
+    distance[candidate.0][candidate.1] = 0;
+    let mut queue = VecDeque::from([candidate]);
+    let mut reached = 0;
+    let mut nearest_known: Option<u32> = None;
+    while let Some(current) = queue.pop_front() {
+        let d = distance[current.0][current.1];
+        reached += 1;
+        if nearest_known.is_none() && state.get_tile(current).owner == enemy {
+            nearest_known = Some(d);
+        }
+        // the territory is connected, known land further out than its size can't be part of it
+        let found_earliest = earliest.is_none_or(|(tile, bound)| {
+            distance[tile.0][tile.1] != u32::MAX || d as usize >= bound
+        });
+        if reached >= lands
+            && (nearest_known.is_some() || !knows_enemy_land || d as usize >= lands)
+            && found_earliest
+        {
+            break;
+        }
+        for n in get_neighbors(current, 25, 25) {
+            if distance[n.0][n.1] == u32::MAX && could_be_enemy(state, n) {
+                distance[n.0][n.1] = d + 1;
+                queue.push_back(n);
+            }
+        }
+    }
+
+    if reached < lands {
+        return 0.;
+    }
+    if earliest.is_some_and(|(tile, bound)| distance[tile.0][tile.1] as usize >= bound) {
+        return 0.;
+    }
+    match nearest_known {
+        // nothing seen of the enemy yet, the land count alone says nothing about where
+        None if !knows_enemy_land => 1.,
+        None => 0.,
+        Some(d) if d as usize >= lands => 0.,
+        // the known land is usually the edge of a territory about as wide as the root of its
+        // size, anything within that is as likely as the rest, further out less and less
+        Some(d) => {
+            let radius = (lands as f64).sqrt();
+            (-(d as f64 - radius).max(0.) / radius).exp()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{GameState, Tile};
+
+    // we are at (0, 0) and see the enemy tile at location with the enemy at lands tiles
+    fn sighting(location: Location, lands: u16) -> GeneralsGameState {
+        let mut state: GeneralsGameState = GameState::new(0, (0, 0));
+        state.lands[1] = lands;
+        state.set_tile(location, Tile::new(TileType::Enemy, 1, Some(1)));
+        state.fog_mask[location.0][location.1] = 1;
+        state
+    }
+
+    #[test]
+    fn guesses_the_army_on_a_fogged_general() {
+        let mut state: GeneralsGameState = GameState::new(0, (0, 0));
+        state.turn = 40;
+        state.lands[1] = 5;
+
+        // twenty turns of armies, all of them could still be on the general
+        state.armies[1] = 30;
+        assert_eq!(general_army(&state, (12, 12)), 21);
+        // but not more than the enemy has to spare
+        state.armies[1] = 10;
+        assert_eq!(general_army(&state, (12, 12)), 6);
+
+        state.set_tile((12, 12), Tile::new(TileType::EnemyGeneral, 7, Some(1)));
+        state.fog_mask[12][12] = 1;
+        assert_eq!(general_army(&state, (12, 12)), 7);
+    }
+
+    #[test]
+    fn land_seen_early_rules_out_far_candidates() {
+        let mut state = sighting((20, 20), 3);
+        let mut belief = GeneralBelief::new();
+        belief.update(&state);
+        assert!(belief.probability((21, 20)) > 0.);
+        assert_eq!(belief.probability((10, 20)), 0.);
+        assert_eq!(belief.probability((24, 0)), 0.);
+
+        // the enemy grew, but the tile was taken while it had three tiles
+        state.lands[1] = 20;
+        belief.update(&state);
+        assert!(belief.probability((21, 20)) > 0.);
+        assert_eq!(belief.probability((10, 20)), 0.);
+    }
+
+    #[test]
+    fn falls_back_on_the_spawn_rules_when_everything_is_ruled_out() {
+        // the enemy land is walled in by tiles we see, no general could have grown into it
+        let mut state = sighting((20, 20), 5);
+        for n in get_neighbors((20, 20), 25, 25) {
+            state.set_tile(n, Tile::new(TileType::VisibleEmpty, 0, None));
+            state.fog_mask[n.0][n.1] = 1;
+        }
+        let mut belief = GeneralBelief::new();
+        belief.update(&state);
+
+        let candidates = belief.top(usize::MAX);
+        assert!(!candidates.is_empty());
+        assert!(candidates.iter().all(|(_, p)| *p == candidates[0].1));
+        let total: f64 = candidates.iter().map(|(_, p)| p).sum();
+        assert!((total - 1.).abs() < 1e-9);
+        assert_eq!(belief.probability((10, 20)), belief.probability((24, 0)));
+        assert_eq!(belief.probability((0, 5)), 0.);
+    }
+}
@@ -1,27 +1,46 @@
-use std::{
-    cmp::max,
-    sync::{atomic::AtomicBool, Arc},
-    time::Duration,
-};
+use std::{cmp::max, sync::Arc};
 
 use client::LobbyType;
-use mcts::MctsTree;
+use mcts::{CancellationToken, MctsTree};
 use oxymcts::Evaluator;
-use state::{GameState, GeneralsGameState, Tile, TileType};
+use state::{GameState, GeneralLocation, GeneralsGameState, Tile, TileType};
+use tokio::sync::oneshot;
 use utils::int_to_location;
 
 use crate::{
-    constants::{load_env_vars, THINKING_TIME},
-    mcts::{evaluate_state, GeneralsUctEvaluator},
-    state::SerializedMoveCommand,
+    config::{EngineKind, ScoreWeights, SearchConfig, SelfPlayConfig},
+    constants::load_env_vars,
+    gather::{GatherStep, Gathering},
+    inference::GeneralBelief,
+    mcts::GeneralsUctEvaluator,
+    opening::Opening,
+    state::{MoveCommand, SerializedMoveCommand},
+    strategy::{Goal, Planner},
+    timing::TimeManager,
     utils::location_to_int,
 };
 
+pub mod alphabeta;
 pub mod client;
+pub mod config;
 pub mod constants;
+pub mod determinize;
 pub mod enemy;
+pub mod eval;
+pub mod export;
+pub mod gather;
+pub mod inference;
 pub mod mcts;
+pub mod opening;
+pub mod path;
+pub mod playout;
+pub mod rave;
+pub mod rng;
+pub mod selfplay;
 pub mod state;
+pub mod strategy;
+pub mod timing;
+pub mod transposition;
 pub mod utils;
 
 #[macro_use]
@@ -35,8 +54,44 @@ extern crate tracing;
 
 #[tokio::main]
 async fn main() {
-    // setup tracing subscriber
-    tracing_subscriber::fmt::init();
+    // setup tracing subscriber, LOG_FORMAT=json gives structured logs we can chart
+    if std::env::var("LOG_FORMAT").as_deref() == Ok("json") {
+        tracing_subscriber::fmt().json().init();
+    } else {
+        tracing_subscriber::fmt::init();
+    }
+
+    // `generals_io selfplay` plays bot games offline and fits the linear evaluator instead of connecting
+    if std::env::args().nth(1).as_deref() == Some("selfplay") {
+        let config = SelfPlayConfig::from_env().expect("failed to load self-play config");
+        let search_config = SearchConfig::from_env().expect("failed to load search config");
+        info!("self-play config: {:?}", config);
+        selfplay::run(&config, &search_config).expect("self-play failed");
+        return;
+    }
+
+    // `generals_io arena a.json b.json` plays two search configs against each other offline
+    if std::env::args().nth(1).as_deref() == Some("arena") {
+        let config = SelfPlayConfig::from_env().expect("failed to load self-play config");
+        let weights = Arc::new(ScoreWeights::from_env().expect("failed to load score weights"));
+        let searches = [2, 3].map(|i| {
+            let path = std::env::args()
+                .nth(i)
+                .expect("usage: generals_io arena <search config> <search config>");
+            Arc::new(SearchConfig::load(path).expect("failed to load search config"))
+        });
+        selfplay::arena(&config, searches, weights).expect("arena failed");
+        return;
+    }
+
+    // `generals_io bench` compares root parallel search to the shared tree on self-play positions
+    if std::env::args().nth(1).as_deref() == Some("bench") {
+        let config = SelfPlayConfig::from_env().expect("failed to load self-play config");
+        let search_config = SearchConfig::from_env().expect("failed to load search config");
+        let weights = Arc::new(ScoreWeights::from_env().expect("failed to load score weights"));
+        selfplay::bench(&config, &search_config, weights).expect("bench failed");
+        return;
+    }
 
     // read game id from stdin
     // let mut gameid = String::new();
@@ -52,6 +107,15 @@ async fn main() {
 
     info!("userid: {}", userid);
 
+    let weights = Arc::new(ScoreWeights::from_env().expect("failed to load score weights"));
+    info!("score weights: {:?}", weights);
+
+    let search_config = Arc::new(SearchConfig::from_env().expect("failed to load search config"));
+    info!("search config: {:?}", search_config);
+
+    let evaluator = eval::from_config(&search_config.evaluator, weights.clone())
+        .expect("failed to load leaf evaluator");
+
     loop {
         let mut client = client::GeneralsClient::connect(&userid, &username, &lobby_type).await;
 
@@ -59,6 +123,7 @@ async fn main() {
         let mut update = client.get_game_update().await.unwrap();
 
         let mut update_received_time = std::time::Instant::now();
+        let mut time_manager = TimeManager::new();
 
         info!("game start: {:?}", game_start);
 
@@ -103,15 +168,37 @@ async fn main() {
         update.map_diff.remove(2);
         update.map_diff.remove(2);
 
+        let serialize = |m: &MoveCommand| SerializedMoveCommand {
+            from: location_to_int(m.from, width, height, left_padding, top_padding),
+            to: location_to_int(m.to, width, height, left_padding, top_padding),
+            half: m.half,
+        };
+
         let mut first_update = true;
+        let mut opening: Option<Opening> = None;
+        let mut planner: Option<Planner> = None;
+        let mut belief = GeneralBelief::new();
+        let mut gathering = Gathering::default();
         let mut estimated_next_state: Option<GeneralsGameState> = None;
-
-        let mut mcts = Arc::new(MctsTree::new(&game));
-        let mcts_interruptor = Arc::new(AtomicBool::new(false));
+        let mut last_move: Option<MoveCommand> = None;
+
+        let mut mcts = Arc::new(MctsTree::new(
+            &game,
+            evaluator.clone(),
+            search_config.clone(),
+        ));
+        // stops the search we leave running under our last move while waiting for the server
+        let mut pondering = CancellationToken::new();
+        // its threads, waited for before the next turn touches the tree or starts searching again
+        let mut pondered: Option<oneshot::Receiver<u32>> = None;
         let mut estimated_correct = 0;
+        let mut total_carried_over = 0;
         let mut total_updates = 0;
 
         loop {
+            let previous_game = game.clone();
+            let acknowledged = client.reconcile(&update);
+
             // apply update
             let mut diff_offset = 0;
             let mut cache_offset = 0;
@@ -263,6 +350,14 @@ async fn main() {
                 game.lands[score.player_index as usize] = score.tile_count;
             }
 
+            for m in acknowledged {
+                let from = int_to_location(m.cmd.from, width, height, left_padding, top_padding);
+                let outcome = m.outcome(previous_game.get_tile(from), game.get_tile(from));
+                if outcome == client::MoveOutcome::Rejected {
+                    warn!("move {} ({:?}) was rejected by the server", m.id, m.cmd);
+                }
+            }
+
             if let Some(estimate) = estimated_next_state {
                 let estimate_hash = estimate.get_hash();
                 let actual_hash = game.get_hash();
@@ -271,43 +366,142 @@ async fn main() {
                     info!("estimated hash: {}", estimate_hash);
                     info!("actual hash: {}", actual_hash);
 
-                    client.clear_commands().await;
+                    // our plan was made for the estimated state, drop whatever is still queued from it
+                    if !client.pending_moves().is_empty() {
+                        client.clear_commands().await;
+                    }
                     // warn!(
                     //     "hashes do not match, we are out of sync\nEstimate: {}\n\nActual: {}",
                     //     estimate, game
                     // );
-
-                    mcts = Arc::new(MctsTree::new(&game));
                 } else {
                     estimated_correct += 1;
                 }
-            } else {
-                mcts = Arc::new(MctsTree::new(&game));
             }
 
+            belief.update(&game);
+            if game.turn.is_multiple_of(25)
+                && game.generals()[1 - game.player_id() as usize] == GeneralLocation::Unknown
+            {
+                info!(turn = game.turn, candidates = ?belief.top(3), "enemy general candidates");
+            }
+
+            if first_update && search_config.planner {
+                planner = Some(Planner::new(&game, &search_config, &belief));
+            }
+            // statistics gathered for another goal are no use for the new one
+            let goal_changed = planner
+                .as_mut()
+                .is_some_and(|planner| planner.update(&game, &belief));
+
             total_updates += 1;
 
+            if first_update && search_config.opening_turns > 0 {
+                // it tries every way to send the waves, which would hold up the websocket
+                let (start, end) = (game.clone(), search_config.opening_turns);
+                opening = tokio::task::spawn_blocking(move || Opening::plan(&start, end))
+                    .await
+                    .expect("opening book planning panicked");
+            }
+            let book_moves = opening
+                .as_mut()
+                .and_then(|book| book.step(&game, client.pending_moves().is_empty()));
+            if book_moves.is_none() && opening.take().is_some() {
+                info!("opening book done at turn {}", game.turn);
+                // whatever the book still has queued would get in the way of the search
+                if !client.pending_moves().is_empty() {
+                    client.clear_commands().await;
+                }
+            }
+
+            // while the planner wants army gathered on its staging tile, the whole gather is queued
+            // at once and played out before we search again
+            let staging = match planner.as_ref().map(|planner| planner.plan().goal) {
+                Some(Goal::Gather { staging }) => Some(staging),
+                _ => None,
+            };
+            let step = book_moves.is_none().then(|| {
+                gathering.step(
+                    &game,
+                    staging,
+                    client.pending_moves().is_empty(),
+                    search_config.gather_turns,
+                )
+            });
+            let gather_moves = match step {
+                Some(GatherStep::Wait) => Some(vec![]),
+                Some(GatherStep::Start(gather)) => {
+                    info!(
+                        "gathering {} army on {:?} in {} moves",
+                        gather.army,
+                        gather.target,
+                        gather.moves.len()
+                    );
+                    Some(gather.moves)
+                }
+                Some(GatherStep::Stop) => {
+                    if !client.pending_moves().is_empty() {
+                        client.clear_commands().await;
+                    }
+                    None
+                }
+                Some(GatherStep::Search) | None => None,
+            };
+
             let moves = game.get_possible_commands();
-            //&& !first_update
-            if !moves.is_empty() {
+            if let Some(queued) = book_moves.or(gather_moves) {
+                if !queued.is_empty() {
+                    info!("queueing {:?}", queued);
+                }
+                for m in &queued {
+                    client.send_cmd(serialize(m)).await;
+                }
+                // the queue plays on its own, there is no single move to predict or reuse a tree for
+                estimated_next_state = None;
+                last_move = None;
+            } else if !moves.is_empty() {
+                // the tree is only needed for turns we search, keep the statistics of the last
+                // search if it predicted this position
+                mcts = match last_move
+                    .as_ref()
+                    .filter(|_| !goal_changed)
+                    .and_then(|played| mcts.promote(played, &game))
+                {
+                    Some(promoted) => {
+                        total_carried_over += promoted.carried_over() as u64;
+                        Arc::new(promoted)
+                    }
+                    None => Arc::new(mcts.restart(&game, planner.as_ref().map(Planner::plan))),
+                };
                 // // select random one
                 // let move_index = rand::random::<usize>() % moves.len();
                 // let move_command = moves[move_index].clone();
-                let move_command = mcts
-                    .get_best_move(Duration::from_millis(THINKING_TIME))
-                    .await;
-
-                let ser = SerializedMoveCommand {
-                    from: location_to_int(
-                        move_command.from,
-                        width,
-                        height,
-                        left_padding,
-                        top_padding,
-                    ),
-                    to: location_to_int(move_command.to, width, height, left_padding, top_padding),
-                    half: move_command.half,
+                let budget = time_manager.budget(&game, &search_config);
+                let search_start = std::time::Instant::now();
+                let report = match search_config.engine {
+                    EngineKind::Mcts => mcts.get_best_move(budget).await,
+                    EngineKind::AlphaBeta => {
+                        let (game, evaluator, config) =
+                            (game.clone(), evaluator.clone(), search_config.clone());
+                        tokio::task::spawn_blocking(move || {
+                            alphabeta::search(&game, evaluator.as_ref(), &config, Some(budget))
+                        })
+                        .await
+                        .expect("alpha-beta search panicked")
+                    }
                 };
+                let move_command = report.best_move.clone();
+                let search_time = search_start.elapsed();
+
+                let ser = serialize(&move_command);
+
+                info!(
+                    rollouts = report.rollouts,
+                    tree_size = report.tree_size,
+                    depth = report.depth,
+                    "search report\n{}",
+                    report
+                );
 
                 if move_command.from == move_command.to {
                     info!("We did a NOOP")
@@ -317,25 +511,55 @@ async fn main() {
 
                 estimated_next_state = Some(game.tick(&move_command).unwrap());
 
-                let score = evaluate_state(estimated_next_state.as_ref().unwrap().clone());
+                let estimate = estimated_next_state.as_ref().unwrap();
+                let breakdown = estimate.get_score_breakdown(&estimate.player_id(), &weights);
 
+                info!("{}\nscore:{}", estimate, breakdown.total());
                 info!(
-                    "{}\nscore:{}",
-                    estimated_next_state.as_ref().unwrap(),
-                    score
+                    turn = estimate.turn,
+                    total = breakdown.total(),
+                    winner = breakdown.winner,
+                    army = breakdown.army,
+                    fog = breakdown.fog,
+                    land = breakdown.land,
+                    general_revealed = breakdown.general_revealed,
+                    army_distance = breakdown.army_distance,
+                    "score breakdown"
                 );
 
                 client.send_cmd(ser).await;
 
-                mcts = Arc::new(MctsTree::new(estimated_next_state.as_ref().unwrap()));
-                mcts_interruptor.store(false, std::sync::atomic::Ordering::SeqCst);
-                let local_mcts = mcts.clone();
-                let local_interruptor = mcts_interruptor.clone();
-                tokio::spawn(
-                    async move { local_mcts.train_until_interrupt(local_interruptor).await },
-                );
+                // alpha-beta keeps no tree to export or to search on while we wait
+                let mcts_engine = search_config.engine == EngineKind::Mcts;
+
+                if search_config.export_tree && mcts_engine {
+                    let dir =
+                        std::path::Path::new(&search_config.log_dir).join(&game_start.replay_id);
+                    let exported =
+                        mcts.export(search_config.export_depth, search_config.export_min_visits);
+                    if let Err(e) = exported.write(&dir, game.turn) {
+                        warn!("failed to export search tree: {:?}", e);
+                    }
+                }
+                time_manager
+                    .observe_overhead(update_received_time.elapsed().saturating_sub(search_time));
+
+                last_move = Some(move_command);
+
+                // keep searching under our move while we wait, that subtree gets reused next turn
+                // a fixed iteration count means reproducible decisions, so no searching on the side then
+                let ponder = last_move
+                    .as_ref()
+                    .filter(|_| search_config.iterations == 0 && mcts_engine)
+                    .and_then(|played| mcts.after(played));
+                if let Some(ponder) = ponder {
+                    mcts = Arc::new(ponder);
+                    pondering = CancellationToken::new();
+                    pondered = Some(mcts.spawn_search(pondering.clone(), false));
+                }
             } else {
                 estimated_next_state = None;
+                last_move = None;
                 info!("{}", game);
             }
 
@@ -348,12 +572,17 @@ async fn main() {
             }
             update = recv.unwrap();
 
-            mcts_interruptor.store(true, std::sync::atomic::Ordering::SeqCst);
+            pondering.cancel();
+            if let Some(pondered) = pondered.take() {
+                // the rollout count doesn't matter, only that the threads are done
+                let _ = pondered.await;
+            }
 
             let end = std::time::Instant::now();
             let waited = end - start;
             let time_between_updates = end - update_received_time;
             update_received_time = end;
+            time_manager.observe_update(time_between_updates);
 
             info!(
                 "waited for update: {:?}, time between updates: {:?}",
@@ -361,7 +590,17 @@ async fn main() {
             );
         }
 
-        mcts_interruptor.store(true, std::sync::atomic::Ordering::SeqCst);
+        pondering.cancel();
+        if let Some(pondered) = pondered.take() {
+            let _ = pondered.await;
+        }
         info!("estimated correct: {}/{}", estimated_correct, total_updates);
+        if let Some(planner) = &planner {
+            info!("goal transitions: {:?}", planner.transitions());
+        }
+        info!(
+            "rollouts carried over between turns: {}",
+            total_carried_over
+        );
     }
 }
@@ -1,134 +1,742 @@
 use noisy_float::prelude::{n64, Float};
-use num_traits::{float::FloatConst, ToPrimitive, Zero};
+use num_traits::ToPrimitive;
 use oxymcts::{
-    uct_value, BackPropPolicy, DefaultBackProp, DefaultLazyTreePolicy, DefaultPlayout, Evaluator,
-    GameTrait, LazyMcts, LazyMctsNode, LazyMctsTree, LazyTreePolicy, MctsNode, Nat, NodeId,
-    NodeMut, Num, Playout, Tree,
+    uct_value, BackPropPolicy, DefaultBackProp, DefaultPlayout, Evaluator, GameTrait, LazyMcts,
+    LazyMctsNode, LazyMctsTree, LazyTreePolicy, MctsNode, Nat, NodeId, NodeMut, NodeRef, Num,
+    Playout, Tree,
 };
-use rand::{seq::SliceRandom, thread_rng, Rng};
+use rand::Rng;
 use std::{
-    collections::hash_map::DefaultHasher,
+    collections::{hash_map::DefaultHasher, HashSet},
+    fmt::{Display, Formatter},
     hash::{Hash, Hasher},
     marker::PhantomData,
-    ops::{Add, AddAssign, Div},
     sync::{
         atomic::{AtomicBool, AtomicU32, Ordering},
         Arc,
     },
     time::Duration,
 };
+use tokio::sync::oneshot;
 
 use crate::{
-    constants::{MAX_TURNS, THREAD_COUNT},
+    config::SearchConfig,
+    constants::MAX_TURNS,
+    determinize,
     enemy::{possible_enemy_moves, EnemyMove},
+    eval::LeafEvaluator,
+    export::ExportedNode,
+    playout::{self, PlayoutPolicy},
+    rave::{self, RaveStats},
+    rng,
     state::{GameState, GeneralsGameState, MoveCommand, PlayerId},
+    strategy::Plan,
+    transposition::{self, TranspositionTable},
 };
 
 type OxyTree<State> = LazyMcts<
     State,
-    DefaultLazyTreePolicy<State, GeneralsUctEvaluator, (), f64>,
+    GeneralsTreePolicy,
     GeneralsPlayout,
     GeneralsBackProp,
     GeneralsUctEvaluator,
-    (),
+    RaveStats,
     f64,
 >;
 
+type SearchTree = Tree<LazyMctsNode<GameStateWrapper, f64, RaveStats>>;
+
+// tells running search threads to stop after their current iteration
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
 pub struct MctsTree {
     tree: OxyTree<GameStateWrapper>,
+    // in root parallel mode the other threads search these, each on its own copy of the root
+    workers: Vec<OxyTree<GameStateWrapper>>,
+    evaluator: Arc<dyn LeafEvaluator>,
+    config: Arc<SearchConfig>,
+    // one per tree, so the trees of root parallel workers stay independent, empty when the table is off
+    tables: Vec<Arc<TranspositionTable>>,
+    // the strategic goal the search is steered towards, if the planner is on
+    plan: Option<Plan>,
+    carried_over: u32,
 }
 impl MctsTree {
-    pub fn new(state: &GeneralsGameState) -> Self {
-        let wrapped = GameStateWrapper {
-            state: state.clone(),
-            turn: state.player_id(),
+    pub fn new(
+        state: &GeneralsGameState,
+        evaluator: Arc<dyn LeafEvaluator>,
+        config: Arc<SearchConfig>,
+    ) -> Self {
+        Self::with_plan(state, evaluator, config, None)
+    }
+
+    pub fn with_plan(
+        state: &GeneralsGameState,
+        evaluator: Arc<dyn LeafEvaluator>,
+        config: Arc<SearchConfig>,
+        plan: Option<Plan>,
+    ) -> Self {
+        // the memory budget is split between the trees
+        let trees = 1 + Self::worker_count(&config);
+        let tables = if config.transposition_table_mb > 0 {
+            (0..trees)
+                .map(|_| {
+                    Arc::new(TranspositionTable::with_memory_budget(
+                        (config.transposition_table_mb / trees).max(1),
+                    ))
+                })
+                .collect()
+        } else {
+            vec![]
         };
+        Self::with_tables(state, evaluator, config, plan, tables)
+    }
 
+    // a fresh tree on another root that keeps this one's transposition tables, allocating and zeroing
+    // new ones costs more than a short search, and the old search's entries age out as the new one fills them
+    pub fn restart(&self, state: &GeneralsGameState, plan: Option<Plan>) -> Self {
+        self.tables.iter().for_each(|table| table.new_search());
+        Self::with_tables(
+            state,
+            self.evaluator.clone(),
+            self.config.clone(),
+            plan,
+            self.tables.clone(),
+        )
+    }
+
+    fn with_tables(
+        state: &GeneralsGameState,
+        evaluator: Arc<dyn LeafEvaluator>,
+        config: Arc<SearchConfig>,
+        plan: Option<Plan>,
+        tables: Vec<Arc<TranspositionTable>>,
+    ) -> Self {
         Self {
-            tree: OxyTree::new(wrapped),
+            tree: OxyTree::new(Self::wrap_root(state, &evaluator, &config, plan)),
+            workers: (0..Self::worker_count(&config))
+                .map(|_| OxyTree::new(Self::wrap_root(state, &evaluator, &config, plan)))
+                .collect(),
+            evaluator,
+            config,
+            tables,
+            plan,
+            carried_over: 0,
         }
     }
 
-    pub async fn train_until_interrupt(&self, interrupt: Arc<AtomicBool>) {
-        let tree_ref = &self.tree;
-        let c = f64::SQRT_2();
-        let rollout_count = Arc::new(AtomicU32::new(0));
+    // threads past the first one, when they get their own trees
+    fn worker_count(config: &SearchConfig) -> usize {
+        if config.root_parallel && config.iterations == 0 {
+            config.threads.saturating_sub(1)
+        } else {
+            0
+        }
+    }
 
-        let start = std::time::Instant::now();
-        unsafe {
-            // unsafe block because forgetting is unsafe, but we await it so its fine
-            async_scoped::TokioScope::scope_and_collect(|s| {
-                for _ in 0..THREAD_COUNT {
-                    let proc = || async {
-                        loop {
-                            tree_ref.execute(&c, ());
-                            rollout_count.fetch_add(1, Ordering::Relaxed);
-                            if interrupt.load(Ordering::Relaxed) {
-                                break;
-                            }
-                        }
-                    };
+    fn trees(&self) -> impl Iterator<Item = &OxyTree<GameStateWrapper>> {
+        std::iter::once(&self.tree).chain(&self.workers)
+    }
+
+    // for the first tree, the others swap in their own table
+    fn search_args(&self) -> SearchArgs {
+        SearchArgs {
+            c: self.config.exploration,
+            table: self.tables.first().cloned(),
+            determinize: self.config.determinize,
+            widening: self
+                .config
+                .progressive_widening
+                .then_some((self.config.widening_constant, self.config.widening_exponent)),
+            rave: self.config.rave.then_some(self.config.rave_equivalence),
+        }
+    }
 
-                    s.spawn(proc());
+    fn wrap_root(
+        state: &GeneralsGameState,
+        evaluator: &Arc<dyn LeafEvaluator>,
+        config: &SearchConfig,
+        plan: Option<Plan>,
+    ) -> GameStateWrapper {
+        // the search only looks `horizon` turns ahead of the root
+        let mut state = state.clone();
+        state.max_turn = state.turn + config.horizon;
+
+        GameStateWrapper {
+            turn: state.player_id(),
+            state,
+            evaluator: evaluator.clone(),
+            simultaneous: config.simultaneous_moves,
+            plan,
+        }
+    }
+
+    // rollouts that were inherited from the previous turn's tree
+    pub fn carried_over(&self) -> u32 {
+        self.carried_over
+    }
+
+    // after we played `played` and the server sent us `actual`, look for the enemy response
+    // that leads to the same state, and make its subtree the root of a new tree
+    pub fn promote(&self, played: &MoveCommand, actual: &GeneralsGameState) -> Option<Self> {
+        let (promoted, mut carried_over) = self.promote_tree(&self.tree, played, actual)?;
+        let wrapped = Self::wrap_root(actual, &self.evaluator, &self.config, self.plan);
+
+        // a worker that didn't see the actual response starts over
+        let mut workers = vec![];
+        for worker in &self.workers {
+            workers.push(match self.promote_tree(worker, played, actual) {
+                Some((promoted, visits)) => {
+                    carried_over += visits;
+                    OxyTree::from_tree(wrapped.clone(), promoted)
                 }
+                None => OxyTree::new(wrapped.clone()),
+            });
+        }
+
+        // the tables survive too, positions from the old search age out as the new one fills them
+        self.tables.iter().for_each(|table| table.new_search());
+
+        Some(Self {
+            tree: OxyTree::from_tree(wrapped, promoted),
+            workers,
+            evaluator: self.evaluator.clone(),
+            config: self.config.clone(),
+            tables: self.tables.clone(),
+            plan: self.plan,
+            carried_over,
+        })
+    }
+
+    fn promote_tree(
+        &self,
+        tree: &OxyTree<GameStateWrapper>,
+        played: &MoveCommand,
+        actual: &GeneralsGameState,
+    ) -> Option<(SearchTree, u32)> {
+        // a pondering tree already starts after our move
+        let after_ours = tree.root_state().turn != actual.player_id();
+        let tree = tree.tree();
+        // node hashes include the player to move, which is us again after the response
+        let actual_hash = GameTrait::hash(&Self::wrap_root(
+            actual,
+            &self.evaluator,
+            &self.config,
+            self.plan,
+        ));
+
+        let response = if self.config.simultaneous_moves {
+            // our move and the response are one joint action
+            tree.root().children().find(|child| {
+                matches!(
+                    child.value().state.last(),
+                    Some(CombinedMoveCommand::Joint(m, _)) if m == played
+                ) && child.value().hash == actual_hash
+            })?
+        } else {
+            let our_move = if after_ours {
+                tree.root()
+            } else {
+                tree.root().children().find(|child| {
+                    matches!(
+                        child.value().state.last(),
+                        Some(CombinedMoveCommand::Friendly(m)) if m == played
+                    )
+                })?
+            };
+            our_move
+                .children()
+                .find(|child| child.value().hash == actual_hash)?
+        };
+
+        // node histories are relative to the root, so the moves leading here are stripped
+        let depth = response.value().state.len();
+        let mut root_value = response.value().clone();
+        root_value.state.clear();
+
+        let mut promoted = Tree::new(root_value);
+        copy_subtree(response, &mut promoted.root_mut(), depth);
+
+        let carried_over = response.value().n_visits;
+        info!(
+            "Promoted subtree after {:?}, carried over {} rollouts",
+            response.value().state.last(),
+            carried_over
+        );
+        Some((promoted, carried_over))
+    }
+
+    // the subtree of our move `played` as a tree of its own, with the enemy's answer at the root,
+    // so searching on while we wait for the server only looks at positions that can still happen
+    // None if the move was never expanded, or in simultaneous mode where there is no position in between
+    pub fn after(&self, played: &MoveCommand) -> Option<Self> {
+        if self.config.simultaneous_moves {
+            return None;
+        }
+        let tree = Self::subtree_after(&self.tree, played)?;
+        let workers = self
+            .workers
+            .iter()
+            .map(|worker| {
+                Self::subtree_after(worker, played)
+                    .unwrap_or_else(|| OxyTree::new(tree.root_state().clone()))
             })
-            .await;
+            .collect();
+
+        Some(Self {
+            tree,
+            workers,
+            evaluator: self.evaluator.clone(),
+            config: self.config.clone(),
+            tables: self.tables.clone(),
+            plan: self.plan,
+            carried_over: 0,
+        })
+    }
+
+    fn subtree_after(
+        tree: &OxyTree<GameStateWrapper>,
+        played: &MoveCommand,
+    ) -> Option<OxyTree<GameStateWrapper>> {
+        let played = CombinedMoveCommand::Friendly(played.clone());
+        let mut root_state = tree.root_state().clone();
+        let tree = tree.tree();
+        let our_move = tree
+            .root()
+            .children()
+            .find(|child| child.value().state.last() == Some(&played))?;
+
+        root_state.do_move(&played);
+        let mut root_value = our_move.value().clone();
+        root_value.state.clear();
+        let mut subtree = Tree::new(root_value);
+        copy_subtree(our_move, &mut subtree.root_mut(), 1);
+        Some(OxyTree::from_tree(root_state, subtree))
+    }
+
+    // the tree down to max_depth moves from the root, skipping nodes with fewer than min_visits
+    pub fn export(&self, max_depth: usize, min_visits: u32) -> ExportedNode {
+        let tree = self.tree.tree();
+        export_node(
+            tree.root(),
+            None,
+            0,
+            max_depth,
+            min_visits,
+            &self.search_args(),
+        )
+    }
+
+    // true once the best root move has so many of the visits that searching on won't change it
+    fn is_decisive(&self) -> bool {
+        let total: u32 = self.trees().map(|t| t.tree().root().value().n_visits).sum();
+        if total < self.config.min_decisive_rollouts {
+            return false;
         }
 
-        let end = start.elapsed();
+        let best = self
+            .root_moves()
+            .iter()
+            .map(|m| m.visits)
+            .max()
+            .unwrap_or(0);
+        best as f64 / total as f64 >= self.config.decisive_visit_share
+    }
+
+    // statistics of our moves at the root, summed over joint actions and the trees of all workers
+    fn root_moves(&self) -> Vec<RootMove> {
+        let mut root_moves: Vec<RootMove> = vec![];
+        for tree in self.trees() {
+            let tree = tree.tree();
+            for child in tree.root().children() {
+                let command = match child.value().state.last() {
+                    Some(CombinedMoveCommand::Friendly(m) | CombinedMoveCommand::Joint(m, _)) => m,
+                    _ => continue,
+                };
+                let (visits, sum_rewards) = (child.value().n_visits, child.value().sum_rewards);
+                match root_moves.iter_mut().find(|m| &m.command == command) {
+                    Some(m) => {
+                        let total = m.mean_score * m.visits as f64 + sum_rewards;
+                        m.visits += visits;
+                        m.mean_score = total / m.visits.max(1) as f64;
+                    }
+                    None => root_moves.push(RootMove {
+                        command: command.clone(),
+                        visits,
+                        mean_score: sum_rewards / visits.max(1) as f64,
+                    }),
+                }
+            }
+        }
+        root_moves
+    }
+
+    // searches on dedicated threads until cancelled, blocking the calling thread
+    // returns how many rollouts were done
+    pub fn search(&self, cancel: &CancellationToken, early_stop: bool) -> u32 {
+        let search_args = self.search_args();
+        let playout_args = PlayoutArgs::from(self.config.as_ref());
+        let rollout_count = AtomicU32::new(0);
+        // a fixed number of iterations runs on one thread, so the result only depends on the seed
+        let threads = if self.config.iterations > 0 {
+            1
+        } else {
+            self.config.threads
+        };
+
+        std::thread::scope(|s| {
+            for thread in 0..threads {
+                let rollout_count = &rollout_count;
+                let playout_args = &playout_args;
+                // threads past the workers share the first tree, and with it the first table
+                let (tree, search_args) = match self.workers.get(thread.wrapping_sub(1)) {
+                    Some(worker) => (
+                        worker,
+                        SearchArgs {
+                            table: self.tables.get(thread).cloned(),
+                            ..search_args.clone()
+                        },
+                    ),
+                    None => (&self.tree, search_args.clone()),
+                };
+                s.spawn(move || {
+                    let _binding = transposition::bind(search_args.table.clone());
+                    let _trace = rave::bind(search_args.rave.is_some());
+                    let _seeding = rng::seed(
+                        self.config
+                            .seed
+                            .map(|seed| seed.wrapping_add(thread as u64)),
+                    );
+                    loop {
+                        tree.execute(&search_args, playout_args.clone());
+                        let count = rollout_count.fetch_add(1, Ordering::Relaxed);
+                        if self.config.iterations > 0 && count + 1 >= self.config.iterations {
+                            break;
+                        }
+                        // one thread is enough to keep an eye on the root
+                        if early_stop
+                            && thread == 0
+                            && count.is_multiple_of(64)
+                            && self.is_decisive()
+                        {
+                            cancel.cancel();
+                        }
+                        if cancel.is_cancelled() {
+                            break;
+                        }
+                    }
+                });
+            }
+        });
+
+        rollout_count.load(Ordering::Relaxed)
+    }
+
+    // runs search on its own threads so the tokio runtime stays free for the websocket,
+    // the rollout count arrives once it was cancelled (or stopped early)
+    pub fn spawn_search(
+        self: &Arc<Self>,
+        cancel: CancellationToken,
+        early_stop: bool,
+    ) -> oneshot::Receiver<u32> {
+        let (sender, receiver) = oneshot::channel();
+        let tree = self.clone();
+        std::thread::spawn(move || {
+            // nobody waiting for the result anymore is fine
+            let _ = sender.send(tree.search(&cancel, early_stop));
+        });
+        receiver
+    }
+
+    pub async fn get_best_move(self: &Arc<Self>, rollout_for: Duration) -> SearchReport {
+        let cancel = CancellationToken::new();
 
+        // with a fixed iteration count the clock doesn't get a say
+        if self.config.iterations == 0 {
+            let cancel = cancel.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(rollout_for).await;
+                cancel.cancel();
+            });
+        }
+
+        let start = std::time::Instant::now();
+        let rollouts = self
+            .spawn_search(cancel, true)
+            .await
+            .expect("search thread panicked");
+
+        let end = start.elapsed();
         info!(
             "Rollout count: {}, rollout/s: {} for {}ms",
-            rollout_count.load(Ordering::Relaxed),
-            rollout_count.load(Ordering::Relaxed) as f64 / end.as_secs_f64(),
+            rollouts,
+            rollouts as f64 / end.as_secs_f64(),
             end.as_millis()
         );
+        self.finish(rollouts)
     }
 
-    pub async fn get_best_move(&self, rollout_for: Duration) -> MoveCommand {
-        let c = f64::SQRT_2();
-        let notifier = Arc::new(AtomicBool::new(false));
-        let interrupt = notifier.clone();
+    // searches on the calling thread, for fixed iteration configs outside of the game loop
+    pub fn decide(&self) -> SearchReport {
+        assert!(
+            self.config.iterations > 0,
+            "deciding without a time limit needs a fixed iteration count"
+        );
+        let rollouts = self.search(&CancellationToken::new(), true);
+        self.finish(rollouts)
+    }
 
-        tokio::spawn(async move {
-            tokio::time::sleep(rollout_for).await;
-            notifier.store(true, Ordering::Relaxed);
+    // searches on the calling thread until the budget is used up, for benchmarks outside of the game loop
+    pub fn decide_for(&self, budget: Duration) -> SearchReport {
+        let cancel = CancellationToken::new();
+        let timer = cancel.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(budget);
+            timer.cancel();
         });
+        let rollouts = self.search(&cancel, false);
+        self.finish(rollouts)
+    }
+
+    // the most visited of our moves, with its visits summed over every enemy reply of a joint action
+    // and over the trees of all workers, a single joint child would pick our move for the reply that
+    // suits us best
+    fn finish(&self, rollouts: u32) -> SearchReport {
+        let best_move = self
+            .root_moves()
+            .into_iter()
+            .max_by_key(|m| m.visits)
+            .expect("no root moves")
+            .command;
+        self.report(best_move, rollouts)
+    }
 
-        self.train_until_interrupt(interrupt).await;
+    fn report(&self, best_move: MoveCommand, rollouts: u32) -> SearchReport {
+        let tree = self.tree.tree();
+        let root = tree.root();
 
-        // info!("{}", tree.write_tree());
+        // follow the most visited child, which alternates between our moves and enemy responses
+        // in root parallel mode this is the first thread's line
+        let mut principal_variation = vec![];
+        let mut node = root;
+        while let Some(child) = node.children().max_by_key(|c| c.value().n_visits) {
+            principal_variation.push(child.value().state.last().unwrap().clone());
+            node = child;
+        }
 
-        let best_move = self.tree.best_move(&c);
+        let mut top_moves = self.root_moves();
+        top_moves.sort_by_key(|m| std::cmp::Reverse(m.visits));
+        top_moves.truncate(self.config.report_top_moves);
 
-        match best_move {
-            CombinedMoveCommand::Friendly(m) => m,
-            CombinedMoveCommand::Enemy(_) => panic!("Best move is enemy move??"),
+        SearchReport {
+            best_move,
+            principal_variation,
+            top_moves,
+            rollouts,
+            root_visits: self.trees().map(|t| t.tree().root().value().n_visits).sum(),
+            tree_size: self.trees().map(|t| t.tree().nodes().count()).sum(),
+            // node histories are relative to the root, so their length is the depth
+            depth: self
+                .trees()
+                .flat_map(|t| t.tree().nodes().map(|n| n.value().state.len()).max())
+                .max()
+                .unwrap_or(0),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RootMove {
+    pub command: MoveCommand,
+    pub visits: u32,
+    pub mean_score: f64,
+}
+
+// what the search thought of the position, for logs and anything that wants to show it
+#[derive(Clone, Debug, Serialize)]
+pub struct SearchReport {
+    pub best_move: MoveCommand,
+    pub principal_variation: Vec<CombinedMoveCommand>,
+    // the most visited root moves, best first
+    pub top_moves: Vec<RootMove>,
+    // done in this search, the root can have more visits carried over from the last turn
+    pub rollouts: u32,
+    pub root_visits: u32,
+    pub tree_size: usize,
+    pub depth: usize,
+}
+
+impl Display for SearchReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "rollouts: {}, root visits: {}, tree size: {}, depth: {}",
+            self.rollouts, self.root_visits, self.tree_size, self.depth
+        )?;
+        for m in &self.top_moves {
+            writeln!(
+                f,
+                "  {:?} -> {:?}{}: {} visits, mean score {:.3}",
+                m.command.from,
+                m.command.to,
+                if m.command.half { " half" } else { "" },
+                m.visits,
+                m.mean_score
+            )?;
+        }
+        write!(f, "pv:")?;
+        for m in &self.principal_variation {
+            match m {
+                CombinedMoveCommand::Friendly(m) => write!(f, " {:?}->{:?}", m.from, m.to)?,
+                CombinedMoveCommand::Enemy(m) => write!(f, " [{:?}]", m)?,
+                CombinedMoveCommand::Joint(ours, theirs) => {
+                    write!(f, " {:?}->{:?} [{:?}]", ours.from, ours.to, theirs)?
+                }
+            }
         }
+        Ok(())
+    }
+}
+
+fn export_node(
+    node: NodeRef<LazyMctsNode<GameStateWrapper, f64, RaveStats>>,
+    parent_visits: Option<Nat>,
+    depth: usize,
+    max_depth: usize,
+    min_visits: u32,
+    args: &SearchArgs,
+) -> ExportedNode {
+    let value = node.value();
+    let (move_, enemy_move) = match value.state.last() {
+        None => (None, None),
+        Some(CombinedMoveCommand::Friendly(m)) => (
+            Some(format!(
+                "{:?} -> {:?}{}",
+                m.from,
+                m.to,
+                if m.half { " half" } else { "" }
+            )),
+            None,
+        ),
+        Some(CombinedMoveCommand::Enemy(m)) => (Some(format!("{:?}", m)), Some(m.kind())),
+        Some(CombinedMoveCommand::Joint(ours, theirs)) => (
+            Some(format!("{:?} -> {:?} | {:?}", ours.from, ours.to, theirs)),
+            Some(theirs.kind()),
+        ),
+    };
+
+    let mut children: Vec<ExportedNode> = if depth < max_depth {
+        node.children()
+            .filter(|c| c.value().n_visits >= min_visits)
+            .map(|c| {
+                export_node(
+                    c,
+                    Some(value.n_visits),
+                    depth + 1,
+                    max_depth,
+                    min_visits,
+                    args,
+                )
+            })
+            .collect()
+    } else {
+        vec![]
+    };
+    children.sort_by_key(|c| std::cmp::Reverse(c.visits));
+
+    ExportedNode {
+        move_,
+        enemy_move,
+        visits: value.n_visits,
+        mean_reward: value.sum_rewards / value.n_visits.max(1) as f64,
+        uct: parent_visits.map(|parent_visits| {
+            GeneralsUctEvaluator::eval_child(value, &0, parent_visits, args).raw()
+        }),
+        children,
+    }
+}
+
+fn copy_subtree(
+    from: NodeRef<LazyMctsNode<GameStateWrapper, f64, RaveStats>>,
+    to: &mut NodeMut<LazyMctsNode<GameStateWrapper, f64, RaveStats>>,
+    depth: usize,
+) {
+    for child in from.children() {
+        let mut value = child.value().clone();
+        value.state.drain(..depth);
+        copy_subtree(child, &mut to.append(value), depth);
     }
 }
 
-#[derive(Debug, Clone, Hash)]
+#[derive(Debug, Clone)]
 struct GameStateWrapper {
     state: GeneralsGameState,
     turn: PlayerId,
+    evaluator: Arc<dyn LeafEvaluator>,
+    // both players move in every node, as one joint action, instead of taking turns
+    simultaneous: bool,
+    plan: Option<Plan>,
 }
 
-pub fn evaluate_state(state: GeneralsGameState) -> f64 {
-    let player = state.player_id();
-    GeneralsUctEvaluator::evaluate_leaf(
-        GameStateWrapper {
-            state,
-            turn: 1 - player,
-        },
-        &player,
-    )
+// the evaluator, the node mode and the plan are the same for the whole search, so they don't take part in the hash
+impl Hash for GameStateWrapper {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        self.state.hash(hasher);
+        self.turn.hash(hasher);
+    }
 }
 
-#[derive(Debug, Clone, Hash)]
-enum CombinedMoveCommand {
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub enum CombinedMoveCommand {
     Friendly(MoveCommand),
     Enemy(EnemyMove),
+    // our move and the enemy's, resolved in the same tick
+    Joint(MoveCommand, EnemyMove),
+}
+
+impl GameStateWrapper {
+    fn friendly_moves(&self) -> Vec<MoveCommand> {
+        // most promising first, progressive widening expands them in this order
+        // moves towards the strategic goal count as much as a good capture
+        let mut commands = self.state.get_possible_commands();
+        commands.sort_by_cached_key(|m| {
+            let bias = self.plan.map_or(0, |plan| plan.move_bias(&self.state, m));
+            std::cmp::Reverse(playout::move_prior(&self.state, m) + bias)
+        });
+        commands
+    }
+
+    fn tick_friendly(&self, m: &MoveCommand) -> GeneralsGameState {
+        // the move was expanded under another determinization, or a simultaneous enemy move hit the tile,
+        // and there's nothing of ours left to move, so we wait
+        let from = self.state.get_tile(m.from);
+        if from.owner != Some(self.state.player_id()) || from.population < 2 {
+            let general = self.state.get_own_general();
+            return self
+                .state
+                .tick(&MoveCommand {
+                    from: general,
+                    to: general,
+                    half: false,
+                })
+                .unwrap();
+        }
+        self.state.tick(m).unwrap()
+    }
 }
 
 impl GameTrait for GameStateWrapper {
@@ -137,9 +745,19 @@ impl GameTrait for GameStateWrapper {
     type Move = CombinedMoveCommand;
 
     fn legals_moves(&self) -> Vec<Self::Move> {
-        let moves = if self.turn == self.state.player_id() {
-            self.state
-                .get_possible_commands()
+        let moves = if self.simultaneous {
+            // ordered by our move first, so widening admits every response to a good move together
+            let enemy_moves = possible_enemy_moves(&self.state, 1 - self.state.player_id());
+            self.friendly_moves()
+                .into_iter()
+                .flat_map(|ours| {
+                    enemy_moves
+                        .iter()
+                        .map(move |theirs| CombinedMoveCommand::Joint(ours.clone(), theirs.clone()))
+                })
+                .collect()
+        } else if self.turn == self.state.player_id() {
+            self.friendly_moves()
                 .into_iter()
                 .map(CombinedMoveCommand::Friendly)
                 .collect()
@@ -158,9 +776,10 @@ impl GameTrait for GameStateWrapper {
         self.turn
     }
 
+    // the player to move is part of the key, an enemy noop leaves the state as it was
     fn hash(&self) -> u64 {
         let mut hasher = DefaultHasher::new();
-        self.state.hash(&mut hasher);
+        Hash::hash(self, &mut hasher);
         hasher.finish()
     }
 
@@ -169,9 +788,17 @@ impl GameTrait for GameStateWrapper {
     }
 
     fn do_move(&mut self, m: &Self::Move) {
+        if let CombinedMoveCommand::Joint(ours, theirs) = m {
+            // the enemy move goes first, then the tick applies ours and grows the armies once
+            self.state = theirs.apply_on_state(&self.state, 1 - self.state.player_id());
+            self.state = self.tick_friendly(ours);
+            return;
+        }
+
         self.state = match m {
-            CombinedMoveCommand::Friendly(m) => self.state.tick(m).unwrap(),
+            CombinedMoveCommand::Friendly(m) => self.tick_friendly(m),
             CombinedMoveCommand::Enemy(m) => m.apply_on_state(&self.state, self.turn),
+            CombinedMoveCommand::Joint(..) => unreachable!(),
         };
         // debug assert that if state.player_id() == self.turn then we are friendly
         debug_assert!(
@@ -190,76 +817,215 @@ impl GameTrait for GameStateWrapper {
     }
 }
 
+#[derive(Clone)]
+pub struct SearchArgs {
+    c: f64,
+    table: Option<Arc<TranspositionTable>>,
+    // sample the fog again for every iteration
+    determinize: bool,
+    // children a node may have, as constant * visits^exponent, None expands every move
+    widening: Option<(f64, f64)>,
+    // rave equivalence parameter, None is plain uct
+    rave: Option<f64>,
+}
+
+impl SearchArgs {
+    fn can_expand(
+        &self,
+        node: &LazyMctsNode<GameStateWrapper, f64, RaveStats>,
+        children: usize,
+    ) -> bool {
+        if !node.can_add_child() {
+            return false;
+        }
+        match self.widening {
+            Some((constant, exponent)) => {
+                let allowed = (constant * (node.n_visits.max(1) as f64).powf(exponent)).ceil();
+                (children as f64) < allowed
+            }
+            None => true,
+        }
+    }
+}
+
 pub struct GeneralsUctEvaluator;
 
-impl Evaluator<GameStateWrapper, f64, ()> for GeneralsUctEvaluator {
-    type Args = f64;
+impl Evaluator<GameStateWrapper, f64, RaveStats> for GeneralsUctEvaluator {
+    type Args = SearchArgs;
     type EvalResult = f64;
 
     fn eval_child(
-        child: &LazyMctsNode<GameStateWrapper, f64, ()>,
+        child: &LazyMctsNode<GameStateWrapper, f64, RaveStats>,
         _turn: &PlayerId,
         parent_visits: Nat,
-        &c: &Self::Args,
+        args: &Self::Args,
     ) -> Num {
         if child.n_visits == 0 {
             return n64(0f64);
         }
-        uct_value(
-            parent_visits,
-            child.sum_rewards.to_f64().unwrap(),
-            child.n_visits,
-            c,
-        )
+
+        // if the position was reached more often through other move orders, trust those statistics
+        let (visits, sum_rewards) = match args.table.as_ref().and_then(|t| t.get(child.hash)) {
+            Some(entry) if entry.visits > child.n_visits => (entry.visits, entry.sum_rewards),
+            _ => (child.n_visits, child.sum_rewards.to_f64().unwrap()),
+        };
+
+        // early on, lean on how the move did anywhere later in the simulations
+        let mean = sum_rewards / visits as f64;
+        let mean = match args.rave {
+            Some(equivalence) if child.additional_info.visits > 0 => {
+                let beta = rave::beta(visits, equivalence);
+                (1. - beta) * mean + beta * child.additional_info.mean()
+            }
+            _ => mean,
+        };
+
+        uct_value(parent_visits, mean * visits as f64, visits, args.c)
     }
 
-    fn evaluate_leaf(child: GameStateWrapper, turn: &PlayerId) -> Self::EvalResult {
-        child.state.get_score(turn)
+    // oxymcts asks for the player to move at the root, which is the enemy in a pondering tree,
+    // but every search is ours and its rewards are always scored for us, the plan's bonus included
+    fn evaluate_leaf(child: GameStateWrapper, _turn: &PlayerId) -> Self::EvalResult {
+        let score = child
+            .evaluator
+            .evaluate(&child.state, child.state.player_id());
+        match child.plan {
+            Some(plan) => score + plan.bonus(&child.state, child.evaluator.score_range()),
+            None => score,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+struct PlayoutArgs {
+    policy: Arc<dyn PlayoutPolicy>,
+    // turns a playout runs before the leaf gets evaluated, 0 plays until the horizon
+    depth: u64,
+}
+impl From<&SearchConfig> for PlayoutArgs {
+    fn from(config: &SearchConfig) -> Self {
+        Self {
+            policy: playout::from_config(config),
+            depth: config.playout_depth,
+        }
     }
 }
 
 struct GeneralsPlayout;
 impl Playout<GameStateWrapper> for GeneralsPlayout {
-    type Args = ();
-
-    fn playout(mut state: GameStateWrapper, _args: ()) -> GameStateWrapper {
-        // let friendly_move = state.player_turn() == state.state.player_id();
+    type Args = PlayoutArgs;
 
-        while !state.is_final() {
-            let moves = state.legals_moves();
-
-            let m = if thread_rng().gen_range(0..10) < 4 {
-                // pick randomly from first 10% of moves
-                moves[0..((moves.len() / 10) + 1)]
-                    .choose(&mut thread_rng())
-                    .unwrap()
+    fn playout(mut state: GameStateWrapper, args: PlayoutArgs) -> GameStateWrapper {
+        let cutoff = if args.depth > 0 {
+            state.state.turn + args.depth
+        } else {
+            u64::MAX
+        };
+        while !state.is_final() && state.state.turn < cutoff {
+            let m = if state.simultaneous {
+                let mut ours = state.state.get_possible_commands();
+                let mut theirs = possible_enemy_moves(&state.state, 1 - state.state.player_id());
+                let (our_index, their_index) = rng::with_rng(|rng| {
+                    (
+                        args.policy.choose(&state.state, &ours, rng),
+                        args.policy.choose_enemy(&state.state, &theirs, rng),
+                    )
+                });
+                CombinedMoveCommand::Joint(
+                    ours.swap_remove(our_index),
+                    theirs.swap_remove(their_index),
+                )
+            } else if state.turn == state.state.player_id() {
+                let mut moves = state.state.get_possible_commands();
+                let index = rng::with_rng(|rng| args.policy.choose(&state.state, &moves, rng));
+                CombinedMoveCommand::Friendly(moves.swap_remove(index))
             } else {
-                moves.choose(&mut thread_rng()).unwrap()
+                let mut moves = possible_enemy_moves(&state.state, state.turn);
+                let index =
+                    rng::with_rng(|rng| args.policy.choose_enemy(&state.state, &moves, rng));
+                CombinedMoveCommand::Enemy(moves.swap_remove(index))
             };
 
-            state.do_move(m);
+            rave::record(&m);
+            state.do_move(&m);
         }
         state
     }
 }
 
 struct GeneralsBackProp;
-impl<
-        T: Clone,
-        Move: Clone,
-        R: Add + AddAssign + Div + Clone + Zero + ToPrimitive,
-        A: Clone + Default,
-    > BackPropPolicy<T, Move, R, A> for GeneralsBackProp
+impl GeneralsBackProp {
+    // credits every child whose move its player also made later in this simulation
+    fn update_rave(
+        tree: &mut LazyMctsTree<GameStateWrapper, f64, RaveStats>,
+        leaf: NodeId,
+        playout: Vec<CombinedMoveCommand>,
+        reward: f64,
+    ) {
+        let history = tree.get(leaf).unwrap().value().state.clone();
+        // with joint actions every depth is ours, otherwise the players alternate
+        let sides = match history.first().or(playout.first()) {
+            Some(CombinedMoveCommand::Joint(..)) => 1,
+            _ => 2,
+        };
+
+        // moves made from the current depth on, split by the side that made them
+        let mut later: [HashSet<CombinedMoveCommand>; 2] = Default::default();
+        for (i, m) in playout.into_iter().enumerate() {
+            later[(history.len() + i) % sides].insert(m);
+        }
+
+        let mut node_id = leaf;
+        loop {
+            let node = tree.get(node_id).unwrap();
+            let depth = node.value().state.len();
+            if depth < history.len() {
+                later[depth % sides].insert(history[depth].clone());
+            }
+
+            let parent = node.parent().map(|p| p.id());
+            let children: Vec<NodeId> = node.children().map(|c| c.id()).collect();
+            for child_id in children {
+                let mut child = tree.get_mut(child_id).unwrap();
+                let child = child.value();
+                if child
+                    .state
+                    .last()
+                    .is_some_and(|m| later[depth % sides].contains(m))
+                {
+                    child.additional_info.visits += 1;
+                    child.additional_info.sum_rewards += reward;
+                }
+            }
+
+            match parent {
+                Some(parent) => node_id = parent,
+                None => break,
+            }
+        }
+    }
+}
+
+impl BackPropPolicy<Vec<CombinedMoveCommand>, CombinedMoveCommand, f64, RaveStats>
+    for GeneralsBackProp
 {
-    fn backprop(tree: &mut Tree<MctsNode<T, Move, R, A>>, leaf: NodeId, reward: R) {
+    fn backprop(
+        tree: &mut LazyMctsTree<GameStateWrapper, f64, RaveStats>,
+        leaf: NodeId,
+        reward: f64,
+    ) {
+        if let Some(playout) = rave::take_trace() {
+            Self::update_rave(tree, leaf, playout, reward);
+        }
+
         let root_id = tree.root().id();
         let mut current_node_id = leaf;
         // Update the branch
         while current_node_id != root_id {
             let mut node_to_update = tree.get_mut(current_node_id).unwrap();
             node_to_update.value().n_visits += 1;
-            node_to_update.value().sum_rewards =
-                node_to_update.value().sum_rewards.clone() + reward.clone();
+            node_to_update.value().sum_rewards += reward;
+            transposition::record(node_to_update.value().hash, reward);
             current_node_id = node_to_update.parent().unwrap().id();
         }
         // Update root
@@ -272,34 +1038,39 @@ impl<
 struct GeneralsTreePolicy {}
 impl GeneralsTreePolicy {
     pub fn select(
-        tree: &mut LazyMctsTree<GameStateWrapper, f64, ()>,
+        tree: &mut LazyMctsTree<GameStateWrapper, f64, RaveStats>,
         turn: &PlayerId,
-        evaluator_args: f64,
+        evaluator_args: &SearchArgs,
     ) -> NodeId {
         let mut current_node_id = tree.root().id();
         while tree.get(current_node_id).unwrap().has_children() {
-            if tree.get(current_node_id).unwrap().value().can_add_child() {
+            let node = tree.get(current_node_id).unwrap();
+            if evaluator_args.can_expand(node.value(), node.children().count()) {
                 return current_node_id;
             } else {
-                current_node_id = Self::best_child(tree, turn, current_node_id, &evaluator_args);
+                current_node_id = Self::best_child(tree, turn, current_node_id, evaluator_args);
             }
         }
         current_node_id
     }
 
     pub fn expand(
-        mut node_to_expand: NodeMut<LazyMctsNode<GameStateWrapper, f64, ()>>,
+        mut node_to_expand: NodeMut<LazyMctsNode<GameStateWrapper, f64, RaveStats>>,
         root_state: GameStateWrapper,
+        evaluator_args: &SearchArgs,
     ) -> (NodeId, GameStateWrapper) {
         let mut new_state = Self::update_state(root_state, &node_to_expand.value().state);
         if !node_to_expand.value().can_add_child() {
             return (node_to_expand.id(), new_state);
         }
         let unvisited_moves = &mut node_to_expand.value().unvisited_moves;
-        let index = thread_rng().gen_range(0..unvisited_moves.len());
-        let move_to_expand = unvisited_moves[index].clone();
-        unvisited_moves[index] = unvisited_moves.last().unwrap().clone();
-        unvisited_moves.pop();
+        let move_to_expand = if evaluator_args.widening.is_some() {
+            // unvisited moves are still ordered by legals_moves, take the most promising one
+            unvisited_moves.remove(0)
+        } else {
+            let index = rng::with_rng(|rng| rng.gen_range(0..unvisited_moves.len()));
+            unvisited_moves.swap_remove(index)
+        };
 
         let mut new_historic = node_to_expand.value().state.clone();
         new_state.do_move(&move_to_expand);
@@ -319,16 +1090,20 @@ impl GeneralsTreePolicy {
     }
 }
 
-impl LazyTreePolicy<GameStateWrapper, GeneralsUctEvaluator, (), f64> for GeneralsTreePolicy {
+impl LazyTreePolicy<GameStateWrapper, GeneralsUctEvaluator, RaveStats, f64> for GeneralsTreePolicy {
     fn tree_policy(
-        tree: &mut LazyMctsTree<GameStateWrapper, f64, ()>,
+        tree: &mut LazyMctsTree<GameStateWrapper, f64, RaveStats>,
         root_state: GameStateWrapper,
-        evaluator_args: &f64,
+        evaluator_args: &SearchArgs,
     ) -> (NodeId, GameStateWrapper) {
         let master_player = root_state.player_turn();
-        let selected_node_id = Self::select(tree, &master_player, *evaluator_args);
+        let mut root_state = root_state;
+        if evaluator_args.determinize {
+            root_state.state = rng::with_rng(|rng| determinize::sample(&root_state.state, rng));
+        }
+        let selected_node_id = Self::select(tree, &master_player, evaluator_args);
         let node = tree.get_mut(selected_node_id).unwrap();
-        Self::expand(node, root_state)
+        Self::expand(node, root_state, evaluator_args)
     }
 
     fn update_state(
@@ -342,10 +1117,10 @@ impl LazyTreePolicy<GameStateWrapper, GeneralsUctEvaluator, (), f64> for General
     }
 
     fn best_child(
-        tree: &LazyMctsTree<GameStateWrapper, f64, ()>,
+        tree: &LazyMctsTree<GameStateWrapper, f64, RaveStats>,
         turn: &PlayerId,
         parent_id: NodeId,
-        eval_args: &f64,
+        eval_args: &SearchArgs,
     ) -> NodeId {
         let parent_node = tree.get(parent_id).unwrap();
         let n_visits = parent_node.value().n_visits;
@@ -358,3 +1133,182 @@ impl LazyTreePolicy<GameStateWrapper, GeneralsUctEvaluator, (), f64> for General
             .id()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::{
+        config::{ScoreWeights, SelfPlayConfig},
+        eval::{self, EvaluatorKind},
+        selfplay::Simulator,
+        state::{GeneralLocation, Tile, TileType},
+        strategy::Goal,
+    };
+
+    // a few turns into a random game, so the general has grown some army and there is something to decide
+    fn position() -> GeneralsGameState {
+        let mut simulator =
+            Simulator::random(&SelfPlayConfig::default(), &mut StdRng::seed_from_u64(3));
+        for _ in 0..20 {
+            simulator.step([None, None]);
+        }
+        simulator.view(0)
+    }
+
+    fn seeded_config() -> Arc<SearchConfig> {
+        Arc::new(SearchConfig {
+            seed: Some(7),
+            iterations: 300,
+            horizon: 20,
+            transposition_table_mb: 1,
+            ..SearchConfig::default()
+        })
+    }
+
+    fn hand_tuned() -> Arc<dyn LeafEvaluator> {
+        eval::from_config(&EvaluatorKind::HandTuned, Arc::new(ScoreWeights::default())).unwrap()
+    }
+
+    // the same position, seed and iteration count has to give the same decision every time
+    #[test]
+    fn seeded_search_is_reproducible() {
+        let position = position();
+        let (config, evaluator) = (seeded_config(), hand_tuned());
+        let decide = || MctsTree::new(&position, evaluator.clone(), config.clone()).decide();
+
+        let first = decide();
+        let second = decide();
+        assert!(first.top_moves.len() > 1, "nothing to choose from");
+        assert_eq!(first.rollouts, 300);
+        assert_eq!(first.best_move, second.best_move);
+        assert_eq!(
+            first.top_moves.iter().map(|m| m.visits).collect::<Vec<_>>(),
+            second
+                .top_moves
+                .iter()
+                .map(|m| m.visits)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    // the enemy general next to an army big enough to take it, any seed has to find the capture,
+    // and a change that stops finding it shows up here with the move it picked instead
+    #[test]
+    fn seeded_search_takes_the_general_in_reach() {
+        let mut state: GeneralsGameState = GameState::new(0, (5, 5));
+        for x in 0..25 {
+            for y in 0..25 {
+                state.fog_mask[x][y] = 1;
+                state.set_tile((x, y), Tile::new(TileType::VisibleEmpty, 0, None));
+            }
+        }
+        state.set_tile((5, 5), Tile::new(TileType::OwnedGeneral, 30, Some(0)));
+        state.set_tile((5, 6), Tile::new(TileType::EnemyGeneral, 5, Some(1)));
+        state.set_general(1, GeneralLocation::Known((5, 6)));
+        state.lands = [1, 1];
+        state.armies = [30, 5];
+
+        let report = MctsTree::new(&state, hand_tuned(), seeded_config()).decide();
+        assert_eq!(
+            report.best_move,
+            MoveCommand {
+                from: (5, 5),
+                to: (5, 6),
+                half: false,
+            }
+        );
+    }
+
+    // the plan is ours, its bonus goes on top of our score whoever is to move and is never taken off
+    // the enemy's, which would push scores out of the evaluator's range
+    #[test]
+    fn plan_bonus_is_only_added_for_us() {
+        let state = position();
+        let evaluator = hand_tuned();
+        let plan = Plan {
+            goal: Goal::Expand,
+            weight: SearchConfig::default().goal_weight,
+        };
+        let score = evaluator.evaluate(&state, state.player_id());
+        let bonus = plan.bonus(&state, evaluator.score_range());
+        assert!(bonus > 0.);
+
+        for turn in [state.player_id(), 1 - state.player_id()] {
+            let leaf = GameStateWrapper {
+                state: state.clone(),
+                turn,
+                evaluator: evaluator.clone(),
+                simultaneous: false,
+                plan: Some(plan),
+            };
+            assert_eq!(
+                GeneralsUctEvaluator::evaluate_leaf(leaf, &turn),
+                score + bonus
+            );
+        }
+    }
+
+    // pondering starts with the enemy to move, its rollouts still have to be scored for us or they
+    // drag down the statistics the next turn's tree inherits
+    #[test]
+    fn pondered_rollouts_score_like_the_search() {
+        let evaluator = hand_tuned();
+        let tree = MctsTree::new(&position(), evaluator.clone(), seeded_config());
+        let played = tree.decide().best_move;
+        let searched = tree
+            .root_moves()
+            .into_iter()
+            .find(|m| m.command == played)
+            .unwrap();
+
+        let ponder = tree.after(&played).unwrap();
+        let root = |tree: &MctsTree| {
+            let tree = tree.tree.tree();
+            let root = tree.root().value();
+            (root.n_visits, root.sum_rewards)
+        };
+        let (visits, sum_rewards) = root(&ponder);
+        ponder.search(&CancellationToken::new(), false);
+        let (pondered_visits, pondered_sum_rewards) = root(&ponder);
+
+        let pondered_mean =
+            (pondered_sum_rewards - sum_rewards) / (pondered_visits - visits) as f64;
+        assert!(
+            (pondered_mean - searched.mean_score).abs() < 0.1 * evaluator.score_range(),
+            "pondered rollouts average {pondered_mean}, the search had {}",
+            searched.mean_score
+        );
+    }
+
+    // node hashes carry the player to move, the position the server sends has to match them
+    #[test]
+    fn promote_finds_the_actual_response() {
+        let tree = MctsTree::new(&position(), hand_tuned(), seeded_config());
+        let played = tree.decide().best_move;
+
+        let (actual, response_visits) = {
+            let search_tree = tree.tree.tree();
+            let our_move = search_tree
+                .root()
+                .children()
+                .find(|c| {
+                    c.value().state.last() == Some(&CombinedMoveCommand::Friendly(played.clone()))
+                })
+                .unwrap();
+            let response = our_move
+                .children()
+                .max_by_key(|c| c.value().n_visits)
+                .unwrap();
+            let state = GeneralsTreePolicy::update_state(
+                tree.tree.root_state().clone(),
+                &response.value().state,
+            );
+            (state.state, response.value().n_visits)
+        };
+
+        let promoted = tree.promote(&played, &actual).unwrap();
+        assert_eq!(promoted.carried_over(), response_visits);
+    }
+}
@@ -0,0 +1,276 @@
+// opening book: the first turns of a 1v1 are a race for land with nobody around, so instead of
+// searching we plan the whole expansion up front and queue it on the server wave by wave
+// a wave waits on the general until it has enough army, then walks out claiming a tile per move
+
+use std::collections::VecDeque;
+
+use rayon::prelude::*;
+
+use crate::{
+    state::{GeneralsGameState, Location, MoveCommand, TileType},
+    utils::manhattan_distance,
+};
+
+// the general army a wave after the first one waits for, tried between these
+const MIN_WAVE_ARMY: u16 = 2;
+const MAX_WAVE_ARMY: u16 = 16;
+
+#[derive(Clone, Debug)]
+struct Wave {
+    // the turn of the state the first move is played on
+    launch: u64,
+    moves: Vec<MoveCommand>,
+}
+
+#[derive(Debug)]
+pub struct Opening {
+    waves: VecDeque<Wave>,
+    end: u64,
+}
+
+impl Opening {
+    // plans the expansion until turn `end` from what we see of the start area
+    pub fn plan(state: &GeneralsGameState, end: u64) -> Option<Self> {
+        if state.turn >= end || enemy_visible(state) {
+            return None;
+        }
+
+        // when the first wave leaves and how big the later ones are is all there is to decide,
+        // small enough to try every combination
+        let candidates: Vec<(u64, u16)> = (state.turn..end)
+            .flat_map(|first| (MIN_WAVE_ARMY..=MAX_WAVE_ARMY).map(move |army| (first, army)))
+            .collect();
+        let (waves, land, army) = candidates
+            .into_par_iter()
+            .map(|(first, army)| simulate(state, end, first, army))
+            .max_by_key(|(_, land, army)| (*land, *army))?;
+
+        info!(
+            "opening book: {} waves, {} land and {} army on the general by turn {}",
+            waves.len(),
+            land,
+            army,
+            end
+        );
+        Some(Self {
+            waves: waves.into(),
+            end,
+        })
+    }
+
+    // the moves to queue now, empty to wait for the queue or the next wave,
+    // None once the book is done and the search takes over
+    pub fn step(
+        &mut self,
+        state: &GeneralsGameState,
+        queue_empty: bool,
+    ) -> Option<Vec<MoveCommand>> {
+        if state.turn >= self.end || enemy_visible(state) {
+            return None;
+        }
+        if !queue_empty {
+            return Some(vec![]);
+        }
+
+        let wave = match self.waves.front() {
+            Some(wave) if wave.launch <= state.turn => wave,
+            Some(_) => return Some(vec![]),
+            None => return None,
+        };
+        // the plan assumed the army it leaves with, something went wrong if it isn't there
+        let from = state.get_tile(wave.moves[0].from);
+        if from.owner != Some(state.player_id()) || from.population < 2 {
+            warn!(
+                "opening book wave can't leave {:?}, handing over",
+                wave.moves[0].from
+            );
+            return None;
+        }
+
+        Some(self.waves.pop_front().unwrap().moves)
+    }
+}
+
+fn enemy_visible(state: &GeneralsGameState) -> bool {
+    state
+        .tiles()
+        .iter()
+        .flatten()
+        .any(|tile| tile.tile_type.is_enemy())
+}
+
+// plays out the book with the first wave leaving on turn `first` and the later ones once the
+// general has `army`, returns the waves with the land and the general army they end up with
+fn simulate(start: &GeneralsGameState, end: u64, first: u64, army: u16) -> (Vec<Wave>, usize, u16) {
+    let general = start.get_own_general();
+    let wait = MoveCommand {
+        from: general,
+        to: general,
+        half: false,
+    };
+
+    let mut state = start.clone();
+    let mut waves = vec![];
+    while state.turn < end {
+        let ready = if waves.is_empty() {
+            state.turn >= first
+        } else {
+            state.get_tile(general).population >= army
+        };
+        if !ready || state.get_tile(general).population < 2 {
+            state = state.tick(&wait).unwrap();
+            continue;
+        }
+
+        let path = wave_path(&state, general);
+        if path.len() < 2 {
+            break;
+        }
+        let mut wave = Wave {
+            launch: state.turn,
+            moves: vec![],
+        };
+        for step in path.windows(2) {
+            if state.turn >= end {
+                break;
+            }
+            let m = MoveCommand {
+                from: step[0],
+                to: step[1],
+                half: false,
+            };
+            state = state.tick(&m).unwrap();
+            wave.moves.push(m);
+        }
+        waves.push(wave);
+    }
+
+    let land = state
+        .tiles()
+        .iter()
+        .flatten()
+        .filter(|tile| tile.owner == Some(state.player_id()))
+        .count();
+    (waves, land, state.get_tile(general).population)
+}
+
+fn claimable(state: &GeneralsGameState, location: Location) -> bool {
+    let tile = state.get_tile(location);
+    tile.owner.is_none()
+        && matches!(
+            tile.tile_type,
+            TileType::VisibleEmpty | TileType::AssumedEmpty
+        )
+}
+
+// free tiles around a location, a walk that keeps them high doesn't run into dead ends
+fn room(state: &GeneralsGameState, location: Location, claimed: &[Location]) -> usize {
+    state
+        .neighbors(location)
+        .into_iter()
+        .filter(|n| claimable(state, *n) && !claimed.contains(n))
+        .count()
+}
+
+// the tiles a wave leaving the general walks: over our land to the border, then claiming
+// as many free tiles as its army lasts, the general included
+fn wave_path(state: &GeneralsGameState, general: Location) -> Vec<Location> {
+    let me = Some(state.player_id());
+    let army = state.get_tile(general).population as usize;
+
+    // paths over our own land, closest border tiles first
+    let mut routes: Vec<Vec<Location>> = vec![vec![general]];
+    let mut visited = vec![general];
+    let mut i = 0;
+    while i < routes.len() {
+        let last = *routes[i].last().unwrap();
+        for n in state.neighbors(last) {
+            if state.get_tile(n).owner == me && !visited.contains(&n) {
+                visited.push(n);
+                let mut route = routes[i].clone();
+                route.push(n);
+                routes.push(route);
+            }
+        }
+        i += 1;
+    }
+
+    let mut best: Vec<Location> = vec![];
+    let mut best_claimed = 0;
+    for route in routes {
+        let border = *route.last().unwrap();
+        for first in state.neighbors(border) {
+            if !claimable(state, first) {
+                continue;
+            }
+
+            // the army left on the general stays behind, every claimed tile keeps one
+            let mut path = route.clone();
+            let mut claimed = vec![first];
+            path.push(first);
+            while claimed.len() < army - 1 {
+                let current = *path.last().unwrap();
+                let next = state
+                    .neighbors(current)
+                    .into_iter()
+                    .filter(|n| claimable(state, *n) && !claimed.contains(n))
+                    .max_by_key(|n| (room(state, *n, &claimed), manhattan_distance(*n, general)));
+                match next {
+                    Some(next) => {
+                        claimed.push(next);
+                        path.push(next);
+                    }
+                    None => break,
+                }
+            }
+
+            // more land first, then less walking over our own
+            if claimed.len() > best_claimed
+                || (claimed.len() == best_claimed && path.len() < best.len())
+            {
+                best_claimed = claimed.len();
+                best = path;
+            }
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+    use crate::{config::SelfPlayConfig, selfplay::Simulator};
+
+    // the book is played on a map padded into the 25x25 board, every move it queues has to be one
+    // the server would take on the turn it gets to it
+    #[test]
+    fn book_moves_are_legal() {
+        for seed in 0..4 {
+            let mut simulator =
+                Simulator::random(&SelfPlayConfig::default(), &mut StdRng::seed_from_u64(seed));
+            let mut book = Opening::plan(&simulator.view(0), 50).unwrap();
+            let mut queue: VecDeque<MoveCommand> = VecDeque::new();
+            let mut played = 0;
+            loop {
+                let view = simulator.view(0);
+                match book.step(&view, queue.is_empty()) {
+                    Some(moves) => queue.extend(moves),
+                    None => break,
+                }
+                let m = queue.pop_front();
+                if let Some(m) = &m {
+                    assert!(
+                        view.get_possible_commands().contains(m),
+                        "seed {seed} turn {}: {m:?} is not legal",
+                        view.turn
+                    );
+                    played += 1;
+                }
+                simulator.step([m, None]);
+            }
+            assert!(played > 0, "seed {seed}: the book played nothing");
+        }
+    }
+}
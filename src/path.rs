@@ -0,0 +1,282 @@
+// pathfinding over occupiable tiles
+// unweighted distances count moves, weighted distances also count the army lost capturing tiles
+// the terrain distances are cached on the state and cheap enough for every leaf and playout step,
+// the weighted ones change with every army and are computed where a decision needs them
+
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, VecDeque},
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use crate::{
+    state::{GameState, Location, PlayerId, Tile},
+    utils::{get_neighbors, manhattan_distance},
+};
+
+pub const UNREACHABLE: u32 = u32::MAX;
+
+#[derive(Clone, Debug)]
+pub struct DistanceField<const W: usize, const H: usize> {
+    // indexed [x][y] like locations
+    distances: [[u32; H]; W],
+}
+impl<const W: usize, const H: usize> DistanceField<W, H> {
+    fn unreachable() -> Self {
+        Self {
+            distances: [[UNREACHABLE; H]; W],
+        }
+    }
+
+    #[inline]
+    pub fn get(&self, location: Location) -> Option<u32> {
+        let distance = self.distances[location.0][location.1];
+        if distance == UNREACHABLE {
+            None
+        } else {
+            Some(distance)
+        }
+    }
+
+    #[inline]
+    pub fn get_or_unreachable(&self, location: Location) -> u32 {
+        self.distances[location.0][location.1]
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Path {
+    pub cost: u32,
+    pub tiles: Vec<Location>,
+}
+
+// army needed to walk onto a tile: a move, plus whatever defends it if we don't own it
+#[inline]
+pub fn capture_cost(tile: &Tile, player_id: PlayerId) -> u32 {
+    if tile.owner == Some(player_id) {
+        1
+    } else {
+        1 + tile.population as u32
+    }
+}
+
+pub fn bfs<const P: usize, const W: usize, const H: usize>(
+    state: &GameState<P, W, H>,
+    sources: &[Location],
+) -> DistanceField<W, H> {
+    let mut field = DistanceField::unreachable();
+    let mut queue = VecDeque::with_capacity(W * H);
+
+    for source in sources {
+        field.distances[source.0][source.1] = 0;
+        queue.push_back(*source);
+    }
+
+    while let Some(current) = queue.pop_front() {
+        let distance = field.distances[current.0][current.1];
+        for n in get_neighbors(current, W, H) {
+            if field.distances[n.0][n.1] == UNREACHABLE && state.get_tile(n).tile_type.occupiable()
+            {
+                field.distances[n.0][n.1] = distance + 1;
+                queue.push_back(n);
+            }
+        }
+    }
+
+    field
+}
+
+// distances from sources, paying the capture cost of every tile entered
+pub fn dijkstra_from<const P: usize, const W: usize, const H: usize>(
+    state: &GameState<P, W, H>,
+    sources: &[Location],
+    player_id: PlayerId,
+) -> DistanceField<W, H> {
+    dijkstra(state, sources, player_id, false)
+}
+
+// distances towards target, so the cost of entering the starting tile is not paid
+pub fn dijkstra_to<const P: usize, const W: usize, const H: usize>(
+    state: &GameState<P, W, H>,
+    target: Location,
+    player_id: PlayerId,
+) -> DistanceField<W, H> {
+    dijkstra(state, &[target], player_id, true)
+}
+
+fn dijkstra<const P: usize, const W: usize, const H: usize>(
+    state: &GameState<P, W, H>,
+    sources: &[Location],
+    player_id: PlayerId,
+    reverse: bool,
+) -> DistanceField<W, H> {
+    let mut field = DistanceField::unreachable();
+    let mut heap = BinaryHeap::with_capacity(W * H);
+
+    for source in sources {
+        field.distances[source.0][source.1] = 0;
+        heap.push(Reverse((0, *source)));
+    }
+
+    while let Some(Reverse((distance, current))) = heap.pop() {
+        if distance > field.distances[current.0][current.1] {
+            continue;
+        }
+
+        for n in get_neighbors(current, W, H) {
+            let tile = state.get_tile(n);
+            if !tile.tile_type.occupiable() {
+                continue;
+            }
+
+            // walking backwards, the move n -> current pays for entering current
+            let cost = if reverse {
+                capture_cost(state.get_tile(current), player_id)
+            } else {
+                capture_cost(tile, player_id)
+            };
+
+            let new_distance = distance.saturating_add(cost);
+            if new_distance < field.distances[n.0][n.1] {
+                field.distances[n.0][n.1] = new_distance;
+                heap.push(Reverse((new_distance, n)));
+            }
+        }
+    }
+
+    field
+}
+
+// cheapest path between two tiles, every step costs at least 1 so manhattan distance is admissible
+pub fn astar<const P: usize, const W: usize, const H: usize>(
+    state: &GameState<P, W, H>,
+    from: Location,
+    to: Location,
+    player_id: PlayerId,
+) -> Option<Path> {
+    let mut costs = [[UNREACHABLE; H]; W];
+    let mut came_from: [[Option<Location>; H]; W] = [[None; H]; W];
+    let mut heap = BinaryHeap::new();
+
+    costs[from.0][from.1] = 0;
+    heap.push(Reverse((manhattan_distance(from, to) as u32, 0u32, from)));
+
+    while let Some(Reverse((_, cost, current))) = heap.pop() {
+        if current == to {
+            let mut tiles = vec![current];
+            let mut step = current;
+            while let Some(previous) = came_from[step.0][step.1] {
+                tiles.push(previous);
+                step = previous;
+            }
+            tiles.reverse();
+            return Some(Path { cost, tiles });
+        }
+        if cost > costs[current.0][current.1] {
+            continue;
+        }
+
+        for n in get_neighbors(current, W, H) {
+            let tile = state.get_tile(n);
+            if !tile.tile_type.occupiable() {
+                continue;
+            }
+
+            let new_cost = cost + capture_cost(tile, player_id);
+            if new_cost < costs[n.0][n.1] {
+                costs[n.0][n.1] = new_cost;
+                came_from[n.0][n.1] = Some(current);
+                heap.push(Reverse((
+                    new_cost + manhattan_distance(n, to) as u32,
+                    new_cost,
+                    n,
+                )));
+            }
+        }
+    }
+
+    None
+}
+
+// terrain fields are shared between clones of a state, so search nodes reuse what the root already
+// computed, they only depend on which tiles are occupiable and update_tile resets them when that changes
+#[derive(Clone, Debug, Default)]
+pub struct PathCache<const W: usize, const H: usize> {
+    terrain_from_general: Arc<OnceLock<DistanceField<W, H>>>,
+    terrain_to: Arc<RwLock<HashMap<Location, Arc<DistanceField<W, H>>>>>,
+}
+
+impl<const P: usize, const W: usize, const H: usize> GameState<P, W, H> {
+    // move count from our general, ignoring armies
+    pub fn general_distances(&self) -> &DistanceField<W, H> {
+        self.paths
+            .terrain_from_general
+            .get_or_init(|| bfs(self, &[self.get_own_general()]))
+    }
+
+    // move count to target, ignoring armies, cheap enough to look up during playouts
+    pub fn terrain_distances_to(&self, target: Location) -> Arc<DistanceField<W, H>> {
+        if let Some(field) = self.paths.terrain_to.read().unwrap().get(&target) {
+            return field.clone();
+        }
+
+        let field = Arc::new(bfs(self, &[target]));
+        self.paths
+            .terrain_to
+            .write()
+            .unwrap()
+            .insert(target, field.clone());
+        field
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::TileType;
+
+    fn board(mountains: &[Location]) -> GameState<2, 5, 5> {
+        let mut state = GameState::new(0, (0, 0));
+        for m in mountains {
+            state = state.update_tile(*m, Tile::new(TileType::VisibleMountain, 0, None));
+        }
+        state
+    }
+
+    // with nothing to capture every step costs 1, so the weighted distances are the move counts
+    #[test]
+    fn dijkstra_matches_bfs_without_armies() {
+        // (4, 4) is walled off
+        let state = board(&[(1, 1), (1, 2), (3, 1), (3, 4), (4, 3)]);
+        let moves = bfs(&state, &[(0, 0)]);
+        let weighted = dijkstra_from(&state, &[(0, 0)], 0);
+        for x in 0..5 {
+            for y in 0..5 {
+                assert_eq!(moves.get((x, y)), weighted.get((x, y)), "at {:?}", (x, y));
+            }
+        }
+        assert_eq!(weighted.get((4, 4)), None);
+        assert_eq!(weighted.get((1, 1)), None);
+    }
+
+    // a wall with two gaps, the short way through a city and the long way around it
+    #[test]
+    fn weighted_paths_walk_around_cities() {
+        let state = board(&[(2, 0), (2, 1), (2, 2)])
+            .update_tile((2, 3), Tile::new(TileType::VisibleNeutralCity, 40, None));
+
+        assert_eq!(bfs(&state, &[(4, 0)]).get((0, 0)), Some(10));
+        assert_eq!(dijkstra_to(&state, (4, 0), 0).get((0, 0)), Some(12));
+
+        let path = astar(&state, (0, 0), (4, 0), 0).unwrap();
+        assert_eq!(path.cost, 12);
+        assert_eq!(path.tiles.len(), 13);
+        assert_eq!(path.tiles.first(), Some(&(0, 0)));
+        assert_eq!(path.tiles.last(), Some(&(4, 0)));
+        assert!(!path.tiles.contains(&(2, 3)));
+        for step in path.tiles.windows(2) {
+            assert!(get_neighbors(step[0], 5, 5).contains(&step[1]));
+            assert!(state.get_tile(step[1]).tile_type.occupiable());
+        }
+    }
+}
@@ -0,0 +1,208 @@
+// policies that pick moves during playouts, selected through the search config
+
+use std::{fmt::Debug, sync::Arc};
+
+use rand::{seq::SliceRandom, Rng, RngCore};
+
+use crate::{
+    config::SearchConfig,
+    enemy::EnemyMove,
+    state::{GeneralLocation, GeneralsGameState, MoveCommand, TileType},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlayoutKind {
+    // mostly random, sometimes from the moves with the biggest armies
+    #[default]
+    Biased,
+    Uniform,
+    GreedyCapture,
+    ExpandLand,
+    // march the biggest armies towards the enemy general if we know where it is
+    Heuristic,
+}
+
+pub trait PlayoutPolicy: Debug + Send + Sync {
+    // index into moves, which are sorted by population like get_possible_commands returns them
+    fn choose(
+        &self,
+        state: &GeneralsGameState,
+        moves: &[MoveCommand],
+        rng: &mut dyn RngCore,
+    ) -> usize;
+
+    fn choose_enemy(
+        &self,
+        _state: &GeneralsGameState,
+        moves: &[EnemyMove],
+        rng: &mut dyn RngCore,
+    ) -> usize {
+        rng.gen_range(0..moves.len())
+    }
+}
+
+pub fn from_config(config: &SearchConfig) -> Arc<dyn PlayoutPolicy> {
+    match config.playout_policy {
+        PlayoutKind::Biased => Arc::new(Biased {
+            chance: config.playout_bias_chance,
+            top_fraction: config.playout_bias_top_fraction,
+        }),
+        PlayoutKind::Uniform => Arc::new(Uniform),
+        PlayoutKind::GreedyCapture => Arc::new(GreedyCapture),
+        PlayoutKind::ExpandLand => Arc::new(ExpandLand),
+        PlayoutKind::Heuristic => Arc::new(Heuristic),
+    }
+}
+
+#[inline]
+fn biased_index(len: usize, chance: f64, top_fraction: f64, rng: &mut dyn RngCore) -> usize {
+    if rng.gen_bool(chance) {
+        // pick randomly from the first few moves
+        let top = (len as f64 * top_fraction) as usize + 1;
+        rng.gen_range(0..top.min(len))
+    } else {
+        rng.gen_range(0..len)
+    }
+}
+
+// picks randomly among the moves with the best score, or any move if nothing scores
+fn best_scoring(
+    moves: &[MoveCommand],
+    rng: &mut dyn RngCore,
+    score: impl Fn(&MoveCommand) -> u32,
+) -> usize {
+    let scores: Vec<u32> = moves.iter().map(score).collect();
+    let best = scores.iter().copied().max().unwrap_or(0);
+    if best == 0 {
+        return rng.gen_range(0..moves.len());
+    }
+
+    let candidates: Vec<usize> = (0..moves.len()).filter(|i| scores[*i] == best).collect();
+    *candidates.choose(rng).unwrap()
+}
+
+// type of the tile m captures, None if it doesn't capture anything
+fn captured_tile(state: &GeneralsGameState, m: &MoveCommand) -> Option<TileType> {
+    let from = state.get_tile(m.from);
+    let to = state.get_tile(m.to);
+    if to.owner == from.owner || from.population <= to.population + 1 {
+        return None;
+    }
+    Some(to.tile_type)
+}
+
+// how good a move looks before it is searched, captures ranked by what they take
+pub fn move_prior(state: &GeneralsGameState, m: &MoveCommand) -> u32 {
+    match captured_tile(state, m) {
+        Some(TileType::EnemyGeneral) => 5,
+        Some(TileType::EnemyCity) => 4,
+        Some(TileType::VisibleNeutralCity | TileType::HiddenNeutralCity) => 3,
+        Some(TileType::Enemy) => 2,
+        Some(_) => 1,
+        None => 0,
+    }
+}
+
+#[derive(Debug)]
+pub struct Biased {
+    pub chance: f64,
+    pub top_fraction: f64,
+}
+impl PlayoutPolicy for Biased {
+    fn choose(
+        &self,
+        _state: &GeneralsGameState,
+        moves: &[MoveCommand],
+        rng: &mut dyn RngCore,
+    ) -> usize {
+        biased_index(moves.len(), self.chance, self.top_fraction, rng)
+    }
+
+    fn choose_enemy(
+        &self,
+        _state: &GeneralsGameState,
+        moves: &[EnemyMove],
+        rng: &mut dyn RngCore,
+    ) -> usize {
+        // invasions come first, so this leans towards the enemy attacking
+        biased_index(moves.len(), self.chance, self.top_fraction, rng)
+    }
+}
+
+#[derive(Debug)]
+pub struct Uniform;
+impl PlayoutPolicy for Uniform {
+    fn choose(
+        &self,
+        _state: &GeneralsGameState,
+        moves: &[MoveCommand],
+        rng: &mut dyn RngCore,
+    ) -> usize {
+        rng.gen_range(0..moves.len())
+    }
+}
+
+#[derive(Debug)]
+pub struct GreedyCapture;
+impl PlayoutPolicy for GreedyCapture {
+    fn choose(
+        &self,
+        state: &GeneralsGameState,
+        moves: &[MoveCommand],
+        rng: &mut dyn RngCore,
+    ) -> usize {
+        best_scoring(moves, rng, |m| match captured_tile(state, m) {
+            Some(TileType::EnemyGeneral) => 1000,
+            Some(TileType::EnemyCity) => 500,
+            Some(TileType::VisibleNeutralCity | TileType::HiddenNeutralCity) => 300,
+            Some(TileType::Enemy) => 100 + state.get_tile(m.to).population as u32,
+            Some(_) => 10,
+            None => 0,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct ExpandLand;
+impl PlayoutPolicy for ExpandLand {
+    fn choose(
+        &self,
+        state: &GeneralsGameState,
+        moves: &[MoveCommand],
+        rng: &mut dyn RngCore,
+    ) -> usize {
+        // new land is worth the same wherever it is, cities cost too much army to count as expansion
+        best_scoring(moves, rng, |m| match captured_tile(state, m) {
+            Some(TileType::VisibleEmpty | TileType::AssumedEmpty) => 2,
+            Some(TileType::Enemy) => 1,
+            _ => 0,
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Heuristic;
+impl PlayoutPolicy for Heuristic {
+    fn choose(
+        &self,
+        state: &GeneralsGameState,
+        moves: &[MoveCommand],
+        rng: &mut dyn RngCore,
+    ) -> usize {
+        let enemy = 1 - state.player_id();
+        let target = match state.generals()[enemy as usize] {
+            GeneralLocation::Known(location) => location,
+            _ => return GreedyCapture.choose(state, moves, rng),
+        };
+
+        // every move that gets an army closer to the general counts, bigger armies count more
+        let distances = state.terrain_distances_to(target);
+        best_scoring(moves, rng, |m| {
+            match (distances.get(m.from), distances.get(m.to)) {
+                (Some(from), Some(to)) if to < from => state.get_tile(m.from).population as u32,
+                _ => 0,
+            }
+        })
+    }
+}
@@ -0,0 +1,57 @@
+// all-moves-as-first statistics: a move is also credited with every simulation where its player
+// played it later on, so moves get a usable value long before their own node has many visits
+
+use std::cell::RefCell;
+
+use crate::mcts::CombinedMoveCommand;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RaveStats {
+    pub visits: u32,
+    pub sum_rewards: f64,
+}
+
+impl RaveStats {
+    pub fn mean(&self) -> f64 {
+        self.sum_rewards / self.visits.max(1) as f64
+    }
+}
+
+// how much of the value comes from the rave statistics, falls off as the node gets its own visits
+// equivalence is the visit count at which both are trusted about the same
+#[inline]
+pub fn beta(visits: u32, equivalence: f64) -> f64 {
+    (equivalence / (3. * visits as f64 + equivalence)).sqrt()
+}
+
+thread_local! {
+    // moves of the current playout, backprop needs them and has no other way to see them
+    static TRACE: RefCell<Option<Vec<CombinedMoveCommand>>> = const { RefCell::new(None) };
+}
+
+pub struct Binding;
+
+impl Drop for Binding {
+    fn drop(&mut self) {
+        TRACE.with(|trace| *trace.borrow_mut() = None);
+    }
+}
+
+// playouts on this thread record their moves until the binding is dropped, if enabled
+pub fn bind(enabled: bool) -> Binding {
+    TRACE.with(|trace| *trace.borrow_mut() = enabled.then(Vec::new));
+    Binding
+}
+
+pub fn record(m: &CombinedMoveCommand) {
+    TRACE.with(|trace| {
+        if let Some(trace) = trace.borrow_mut().as_mut() {
+            trace.push(m.clone());
+        }
+    });
+}
+
+// the moves recorded since the last call, None if recording is off
+pub fn take_trace() -> Option<Vec<CombinedMoveCommand>> {
+    TRACE.with(|trace| trace.borrow_mut().as_mut().map(std::mem::take))
+}
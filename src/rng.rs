@@ -0,0 +1,38 @@
+// randomness used by the search
+// normally thread_rng, but a search config with a seed gives every worker its own seeded generator,
+// so with a single thread and a fixed number of iterations a decision can be reproduced
+
+use std::cell::RefCell;
+
+use rand::{rngs::StdRng, thread_rng, RngCore, SeedableRng};
+
+thread_local! {
+    static SEEDED: RefCell<Option<StdRng>> = const { RefCell::new(None) };
+}
+
+pub struct Seeding;
+
+impl Drop for Seeding {
+    fn drop(&mut self) {
+        SEEDED.with(|seeded| *seeded.borrow_mut() = None);
+    }
+}
+
+// search code on this thread draws from a generator seeded with `seed` until the seeding is dropped
+pub fn seed(seed: Option<u64>) -> Seeding {
+    SEEDED.with(|seeded| *seeded.borrow_mut() = seed.map(StdRng::seed_from_u64));
+    Seeding
+}
+
+// the seeded generator is taken out of its cell while f runs, so a nested call can't hit a borrowed cell,
+// it draws from thread_rng instead, which only costs reproducibility
+pub fn with_rng<T>(f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+    match SEEDED.with(|seeded| seeded.borrow_mut().take()) {
+        Some(mut rng) => {
+            let result = f(&mut rng);
+            SEEDED.with(|seeded| *seeded.borrow_mut() = Some(rng));
+            result
+        }
+        None => f(&mut thread_rng()),
+    }
+}
@@ -0,0 +1,758 @@
+// offline self-play: bots play each other on a full information simulator of the game,
+// positions along the way are logged with the final result and used to fit the linear evaluator
+
+use std::{
+    collections::VecDeque,
+    fs::File,
+    io::{BufWriter, Write},
+    sync::Arc,
+};
+
+use anyhow::{bail, Context, Result};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use rayon::prelude::*;
+
+use crate::{
+    alphabeta,
+    config::{EngineKind, ScoreWeights, SearchConfig, SelfPlayConfig},
+    determinize::MIN_GENERAL_DISTANCE,
+    eval::{self, features, LeafEvaluator, LinearWeights, FEATURE_COUNT, FEATURE_NAMES},
+    inference::GeneralBelief,
+    mcts::MctsTree,
+    playout::{self, PlayoutPolicy},
+    state::{
+        GameState, GeneralLocation, GeneralsGameState, Location, MoveCommand, PlayerId, Tile,
+        TileType,
+    },
+    strategy::{Plan, Planner},
+    utils::{get_neighbors, manhattan_distance},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Terrain {
+    Plain,
+    Mountain,
+    City,
+    General,
+    Padding,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Cell {
+    terrain: Terrain,
+    owner: Option<PlayerId>,
+    army: u16,
+}
+
+// the real game, both players see their own fogged view of it
+#[derive(Clone, Debug)]
+pub struct Simulator {
+    turn: u64,
+    cells: [[Cell; 25]; 25],
+    generals: [Location; 2],
+    alive: [bool; 2],
+}
+
+impl Simulator {
+    pub fn random(config: &SelfPlayConfig, rng: &mut impl Rng) -> Self {
+        loop {
+            if let Some(simulator) = Self::try_random(config, rng) {
+                return simulator;
+            }
+        }
+    }
+
+    fn try_random(config: &SelfPlayConfig, rng: &mut impl Rng) -> Option<Self> {
+        let width = rng.gen_range(config.min_size..=config.max_size);
+        let height = rng.gen_range(config.min_size..=config.max_size);
+        // padded into the middle of the 25x25 board, like the live client does
+        let left = (25 - width) / 2;
+        let top = (25 - height) / 2;
+
+        let mut cells = [[Cell {
+            terrain: Terrain::Padding,
+            owner: None,
+            army: 0,
+        }; 25]; 25];
+        let mut plains = vec![];
+        for (x, column) in cells.iter_mut().enumerate().skip(left).take(width) {
+            for (y, cell) in column.iter_mut().enumerate().skip(top).take(height) {
+                cell.terrain = if rng.gen_bool(config.mountain_density) {
+                    Terrain::Mountain
+                } else {
+                    plains.push((x, y));
+                    Terrain::Plain
+                };
+            }
+        }
+
+        plains.shuffle(rng);
+        let first = plains.pop()?;
+        let position = plains
+            .iter()
+            .position(|l| manhattan_distance(*l, first) >= MIN_GENERAL_DISTANCE)?;
+        let second = plains.swap_remove(position);
+
+        for (player, general) in [first, second].into_iter().enumerate() {
+            cells[general.0][general.1] = Cell {
+                terrain: Terrain::General,
+                owner: Some(player as PlayerId),
+                army: 1,
+            };
+        }
+        for city in plains.iter().take(config.cities) {
+            cells[city.0][city.1] = Cell {
+                terrain: Terrain::City,
+                owner: None,
+                army: rng.gen_range(config.city_army_min..=config.city_army_max),
+            };
+        }
+
+        let simulator = Self {
+            turn: 0,
+            cells,
+            generals: [first, second],
+            alive: [true, true],
+        };
+        simulator.connected(first, second).then_some(simulator)
+    }
+
+    fn connected(&self, from: Location, to: Location) -> bool {
+        let mut seen = [[false; 25]; 25];
+        let mut queue = VecDeque::from([from]);
+        seen[from.0][from.1] = true;
+
+        while let Some(current) = queue.pop_front() {
+            if current == to {
+                return true;
+            }
+            for n in get_neighbors(current, 25, 25) {
+                if !seen[n.0][n.1] && self.passable(n) {
+                    seen[n.0][n.1] = true;
+                    queue.push_back(n);
+                }
+            }
+        }
+        false
+    }
+
+    #[inline]
+    fn cell(&self, location: Location) -> &Cell {
+        &self.cells[location.0][location.1]
+    }
+
+    #[inline]
+    fn passable(&self, location: Location) -> bool {
+        !matches!(
+            self.cell(location).terrain,
+            Terrain::Mountain | Terrain::Padding
+        )
+    }
+
+    pub fn winner(&self) -> Option<PlayerId> {
+        match self.alive {
+            [true, false] => Some(0),
+            [false, true] => Some(1),
+            _ => None,
+        }
+    }
+
+    // lands, army and cities (general included) of a player, the server tells everyone these
+    fn totals(&self, player: PlayerId) -> (u16, u16, u16) {
+        let (mut lands, mut army, mut cities) = (0, 0, 0);
+        for row in self.cells.iter() {
+            for cell in row.iter().filter(|c| c.owner == Some(player)) {
+                lands += 1;
+                army += cell.army;
+                if matches!(cell.terrain, Terrain::City | Terrain::General) {
+                    cities += 1;
+                }
+            }
+        }
+        (lands, army, cities)
+    }
+
+    // how many of the player's tiles see this one, same as the fog mask the client keeps
+    fn seen_by(&self, player: PlayerId, location: Location) -> u8 {
+        let mut count = 0;
+        for x in location.0.saturating_sub(1)..=(location.0 + 1).min(24) {
+            for y in location.1.saturating_sub(1)..=(location.1 + 1).min(24) {
+                if self.cells[x][y].owner == Some(player) {
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+
+    // the game as the player's client would have it, built fresh from what is currently visible
+    pub fn view(&self, player: PlayerId) -> GeneralsGameState {
+        let enemy = 1 - player;
+        let mut view: GeneralsGameState = GameState::new(player, self.generals[player as usize]);
+        view.turn = self.turn;
+
+        for x in 0..25 {
+            for y in 0..25 {
+                let cell = self.cells[x][y];
+                let seen = self.seen_by(player, (x, y));
+                view.fog_mask[x][y] = seen;
+
+                let tile = match (cell.terrain, cell.owner) {
+                    (Terrain::Padding, _) => {
+                        view.fog_mask[x][y] = 1;
+                        Tile::new(TileType::Padding, 0, None)
+                    }
+                    // the server tells us where obstacles are, but not what they are
+                    (Terrain::Mountain | Terrain::City, _) if seen == 0 => {
+                        Tile::new(TileType::HiddenObstacle, 0, None)
+                    }
+                    (_, _) if seen == 0 => Tile::new(TileType::AssumedEmpty, 0, None),
+                    (Terrain::Mountain, _) => Tile::new(TileType::VisibleMountain, 0, None),
+                    (Terrain::City, None) => {
+                        Tile::new(TileType::VisibleNeutralCity, cell.army, None)
+                    }
+                    (Terrain::Plain, None) => Tile::new(TileType::VisibleEmpty, 0, None),
+                    (terrain, Some(owner)) => {
+                        let tile_type = match (terrain, owner == player) {
+                            (Terrain::General, true) => TileType::OwnedGeneral,
+                            (Terrain::City, true) => TileType::OwnedCity,
+                            (_, true) => TileType::OwnedTile,
+                            (Terrain::General, false) => TileType::EnemyGeneral,
+                            (Terrain::City, false) => TileType::EnemyCity,
+                            (_, false) => TileType::Enemy,
+                        };
+                        Tile::new(tile_type, cell.army, Some(owner))
+                    }
+                    (Terrain::General, None) => unreachable!("generals always have an owner"),
+                };
+                view.set_tile((x, y), tile);
+            }
+        }
+
+        for p in [player, enemy] {
+            let (lands, army, cities) = self.totals(p);
+            view.lands[p as usize] = lands;
+            view.armies[p as usize] = army;
+            view.city_count[p as usize] = cities;
+        }
+
+        let enemy_general = self.generals[enemy as usize];
+        if !self.alive[enemy as usize] {
+            view.set_general(enemy, GeneralLocation::Dead(enemy_general));
+        } else if self.seen_by(player, enemy_general) > 0 {
+            view.set_general(enemy, GeneralLocation::Known(enemy_general));
+        }
+        view.general_revealed_to[enemy as usize] =
+            self.seen_by(enemy, self.generals[player as usize]) > 0;
+
+        view
+    }
+
+    fn apply_move(&mut self, player: PlayerId, m: &MoveCommand) {
+        let from = *self.cell(m.from);
+        if !self.alive[player as usize]
+            || from.owner != Some(player)
+            || from.army <= 1
+            || manhattan_distance(m.from, m.to) != 1
+            || !self.passable(m.to)
+        {
+            return;
+        }
+
+        let mut moving = from.army - 1;
+        if m.half {
+            moving /= 2;
+        }
+        self.cells[m.from.0][m.from.1].army -= moving;
+
+        let to = &mut self.cells[m.to.0][m.to.1];
+        if to.owner == Some(player) {
+            to.army += moving;
+        } else if moving > to.army {
+            let previous_owner = to.owner;
+            to.army = moving - to.army;
+            to.owner = Some(player);
+
+            if to.terrain == Terrain::General {
+                to.terrain = Terrain::City;
+                self.capture_general(player, previous_owner.unwrap());
+            }
+        } else {
+            to.army -= moving;
+        }
+    }
+
+    // the loser's whole territory goes over to the capturer, with half its army
+    fn capture_general(&mut self, capturer: PlayerId, loser: PlayerId) {
+        self.alive[loser as usize] = false;
+        for row in self.cells.iter_mut() {
+            for cell in row.iter_mut().filter(|c| c.owner == Some(loser)) {
+                cell.owner = Some(capturer);
+                cell.army = cell.army.div_ceil(2);
+            }
+        }
+    }
+
+    // one server tick, both players move and then everything grows like GameState::tick expects
+    pub fn step(&mut self, moves: [Option<MoveCommand>; 2]) {
+        // alternate who goes first so neither side always wins the races
+        let first = (self.turn % 2) as PlayerId;
+        for player in [first, 1 - first] {
+            if let Some(m) = &moves[player as usize] {
+                self.apply_move(player, m);
+            }
+        }
+
+        self.turn += 1;
+        for row in self.cells.iter_mut() {
+            for cell in row.iter_mut().filter(|c| c.owner.is_some()) {
+                if (matches!(cell.terrain, Terrain::City | Terrain::General)
+                    && self.turn.is_multiple_of(2))
+                    || self.turn.is_multiple_of(50)
+                {
+                    cell.army += 1;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sample {
+    pub features: [f64; FEATURE_COUNT],
+    // 1 if the player these features were taken for won
+    pub outcome: f64,
+}
+
+fn choose_move(
+    view: &GeneralsGameState,
+    policy: &dyn PlayoutPolicy,
+    rng: &mut StdRng,
+) -> Option<MoveCommand> {
+    let moves = view.get_possible_commands();
+    // get_possible_commands falls back to a noop on the general when nothing can move
+    if moves[0].from == moves[0].to {
+        return None;
+    }
+    Some(moves[policy.choose(view, &moves, rng)].clone())
+}
+
+fn policies(
+    config: &SelfPlayConfig,
+    search: &SearchConfig,
+    seed: u64,
+) -> [Arc<dyn PlayoutPolicy>; 2] {
+    // swap sides every other game so the map layout doesn't favour one policy
+    let mut kinds = config.policies;
+    if seed % 2 == 1 {
+        kinds.swap(0, 1);
+    }
+    kinds.map(|kind| {
+        playout::from_config(&SearchConfig {
+            playout_policy: kind,
+            ..search.clone()
+        })
+    })
+}
+
+pub fn play_game(config: &SelfPlayConfig, search: &SearchConfig, seed: u64) -> Vec<Sample> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut simulator = Simulator::random(config, &mut rng);
+
+    let policies = policies(config, search, seed);
+
+    let mut positions = vec![];
+    while simulator.winner().is_none() && simulator.turn < config.max_turns {
+        let views = [simulator.view(0), simulator.view(1)];
+        if simulator.turn > 0 && simulator.turn.is_multiple_of(config.sample_every) {
+            for (player, view) in views.iter().enumerate() {
+                positions.push((player as PlayerId, features(view, player as PlayerId)));
+            }
+        }
+
+        let moves = [0, 1].map(|p| choose_move(&views[p], policies[p].as_ref(), &mut rng));
+        simulator.step(moves);
+    }
+
+    let winner = match result(&simulator, config) {
+        Some(winner) => winner,
+        None => return vec![],
+    };
+
+    positions
+        .into_iter()
+        .map(|(player, features)| Sample {
+            features,
+            outcome: if player == winner { 1. } else { 0. },
+        })
+        .collect()
+}
+
+// the winner, or for games stopped at max_turns whoever has the bigger army if config allows it
+fn result(simulator: &Simulator, config: &SelfPlayConfig) -> Option<PlayerId> {
+    if let Some(winner) = simulator.winner() {
+        return Some(winner);
+    }
+    if !config.label_unfinished {
+        return None;
+    }
+
+    let armies = [0, 1].map(|p| simulator.totals(p).1);
+    match armies[0].cmp(&armies[1]) {
+        std::cmp::Ordering::Greater => Some(0),
+        std::cmp::Ordering::Less => Some(1),
+        std::cmp::Ordering::Equal => None,
+    }
+}
+
+// `tree` is the player's previous search, its transposition table is reused for this one
+fn decide(
+    view: &GeneralsGameState,
+    evaluator: &Arc<dyn LeafEvaluator>,
+    search: &Arc<SearchConfig>,
+    plan: Option<Plan>,
+    tree: &mut Option<MctsTree>,
+) -> MoveCommand {
+    match search.engine {
+        EngineKind::Mcts => {
+            let fresh = match tree.take() {
+                Some(previous) => previous.restart(view, plan),
+                None => MctsTree::with_plan(view, evaluator.clone(), search.clone(), plan),
+            };
+            tree.insert(fresh).decide().best_move
+        }
+        EngineKind::AlphaBeta => {
+            // no clock, so the same games come out however busy the machine is
+            alphabeta::search(view, evaluator.as_ref(), search, None).best_move
+        }
+    }
+}
+
+// index of the search config that won, both sides search every tick
+fn play_arena_game(
+    config: &SelfPlayConfig,
+    searches: &[Arc<SearchConfig>; 2],
+    evaluators: &[Arc<dyn LeafEvaluator>; 2],
+    seed: u64,
+) -> Option<usize> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut simulator = Simulator::random(config, &mut rng);
+    // which config plays which side, swapped every other game
+    let sides = if seed.is_multiple_of(2) {
+        [0, 1]
+    } else {
+        [1, 0]
+    };
+    let mut planners: [Option<Planner>; 2] = [None, None];
+    let mut trees: [Option<MctsTree>; 2] = [None, None];
+    let mut beliefs = [GeneralBelief::new(), GeneralBelief::new()];
+
+    while simulator.winner().is_none() && simulator.turn < config.max_turns {
+        let moves = [0, 1].map(|player| {
+            let side = sides[player];
+            let view = simulator.view(player as PlayerId);
+            // every turn, what the player saw while it had nothing to move counts too
+            let belief = &mut beliefs[player];
+            belief.update(&view);
+            let moves = view.get_possible_commands();
+            if moves[0].from == moves[0].to {
+                return None;
+            }
+            let planner = &mut planners[player];
+            match planner {
+                Some(planner) => {
+                    planner.update(&view, belief);
+                }
+                None if searches[side].planner => {
+                    *planner = Some(Planner::new(&view, &searches[side], belief))
+                }
+                None => {}
+            }
+            let plan = planner.as_ref().map(Planner::plan);
+            Some(decide(
+                &view,
+                &evaluators[side],
+                &searches[side],
+                plan,
+                &mut trees[player],
+            ))
+        });
+        simulator.step(moves);
+    }
+
+    result(&simulator, config).map(|winner| sides[winner as usize])
+}
+
+// plays two search configs against each other, to tell whether a change to the search decides better
+// or which engine does, searches run on a single thread for a fixed number of iterations or a fixed
+// depth, the games run in parallel
+pub fn arena(
+    config: &SelfPlayConfig,
+    searches: [Arc<SearchConfig>; 2],
+    weights: Arc<ScoreWeights>,
+) -> Result<[u64; 2]> {
+    for search in &searches {
+        match search.engine {
+            EngineKind::Mcts if search.iterations == 0 => {
+                bail!("arena mcts searches need a fixed number of iterations")
+            }
+            EngineKind::AlphaBeta if search.alpha_beta_depth == 0 => {
+                bail!("arena alpha-beta searches need a fixed depth")
+            }
+            _ => {}
+        }
+    }
+    let evaluators = [
+        eval::from_config(&searches[0].evaluator, weights.clone())?,
+        eval::from_config(&searches[1].evaluator, weights)?,
+    ];
+
+    let results: Vec<Option<usize>> = (0..config.games)
+        .into_par_iter()
+        .map(|i| play_arena_game(config, &searches, &evaluators, config.seed.wrapping_add(i)))
+        .collect();
+
+    let mut wins = [0, 0];
+    for winner in results.iter().flatten() {
+        wins[*winner] += 1;
+    }
+    info!(
+        "arena: first config won {}, second config won {}, {} undecided",
+        wins[0],
+        wins[1],
+        config.games - wins[0] - wins[1]
+    );
+    Ok(wins)
+}
+
+// positions from policy games, taken every sample_every ticks and alternating sides
+fn bench_positions(config: &SelfPlayConfig, search: &SearchConfig) -> Vec<GeneralsGameState> {
+    let mut positions = vec![];
+    let mut seed = config.seed;
+    while positions.len() < config.bench_positions {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut simulator = Simulator::random(config, &mut rng);
+        let policies = policies(config, search, seed);
+
+        while simulator.winner().is_none()
+            && simulator.turn < config.max_turns
+            && positions.len() < config.bench_positions
+        {
+            let views = [simulator.view(0), simulator.view(1)];
+            if simulator.turn > 0 && simulator.turn.is_multiple_of(config.sample_every) {
+                let view = &views[(simulator.turn / config.sample_every % 2) as usize];
+                if view.get_possible_commands()[0].from != view.get_possible_commands()[0].to {
+                    positions.push(view.clone());
+                }
+            }
+
+            let moves = [0, 1].map(|p| choose_move(&views[p], policies[p].as_ref(), &mut rng));
+            simulator.step(moves);
+        }
+        seed = seed.wrapping_add(1);
+    }
+    positions
+}
+
+// compares root parallel search to the shared tree on the same positions: rollouts/s of both, and
+// how often root parallel picks the move the shared tree picks, next to how often two shared tree
+// searches agree with each other, which is as good as it gets with a noisy search
+// both modes pick the move with the most root visits, so only the parallelism differs
+pub fn bench(
+    config: &SelfPlayConfig,
+    search: &SearchConfig,
+    weights: Arc<ScoreWeights>,
+) -> Result<()> {
+    if search.iterations > 0 {
+        bail!("bench compares parallel searches, iterations must be 0");
+    }
+    let evaluator = eval::from_config(&search.evaluator, weights)?;
+    let shared = Arc::new(SearchConfig {
+        root_parallel: false,
+        ..search.clone()
+    });
+    let root_parallel = Arc::new(SearchConfig {
+        root_parallel: true,
+        ..search.clone()
+    });
+
+    let positions = bench_positions(config, search);
+    let mut rollouts = [0u64; 2];
+    let (mut shared_agree, mut root_agree) = (0, 0);
+    // one tree per mode, restarted on every position so its transposition table is only allocated once
+    let Some(start) = positions.first() else {
+        bail!("no positions to bench, bench_positions is 0");
+    };
+    let mut trees = [&shared, &root_parallel]
+        .map(|search| MctsTree::new(start, evaluator.clone(), search.clone()));
+    for position in &positions {
+        let mut decide = |mode: usize| {
+            trees[mode] = trees[mode].restart(position, None);
+            trees[mode].decide_for(search.thinking_time())
+        };
+        let first = decide(0);
+        let second = decide(0);
+        let root = decide(1);
+
+        rollouts[0] += (first.rollouts + second.rollouts) as u64;
+        rollouts[1] += root.rollouts as u64;
+        shared_agree += (first.best_move == second.best_move) as usize;
+        root_agree += (first.best_move == root.best_move) as usize;
+    }
+
+    let seconds = positions.len() as f64 * search.thinking_time().as_secs_f64();
+    info!(
+        "bench over {} positions with {} threads: shared tree {:.0} rollouts/s, root parallel {:.0} rollouts/s",
+        positions.len(),
+        search.threads,
+        rollouts[0] as f64 / (2. * seconds),
+        rollouts[1] as f64 / seconds
+    );
+    info!(
+        "bench: root parallel agrees with the shared tree on {:.1}% of the decisions, the shared tree with itself on {:.1}%",
+        100. * root_agree as f64 / positions.len() as f64,
+        100. * shared_agree as f64 / positions.len() as f64
+    );
+    Ok(())
+}
+
+#[inline]
+fn sigmoid(x: f64) -> f64 {
+    1. / (1. + (-x).exp())
+}
+
+// logistic regression by full batch gradient descent, on standardized features so one
+// learning rate works for all of them, the weights are scaled back before they are returned
+pub fn fit(samples: &[Sample], config: &SelfPlayConfig) -> LinearWeights {
+    let n = samples.len() as f64;
+    let mut mean = [0.; FEATURE_COUNT];
+    let mut std = [0.; FEATURE_COUNT];
+    for sample in samples {
+        for (mean, feature) in mean.iter_mut().zip(sample.features) {
+            *mean += feature / n;
+        }
+    }
+    for sample in samples {
+        for i in 0..FEATURE_COUNT {
+            std[i] += (sample.features[i] - mean[i]).powi(2) / n;
+        }
+    }
+    // features that never change can't be learned, they are left at 0
+    let scale = std.map(|variance| {
+        if variance > 1e-12 {
+            1. / variance.sqrt()
+        } else {
+            0.
+        }
+    });
+
+    let standardized: Vec<[f64; FEATURE_COUNT]> = samples
+        .iter()
+        .map(|s| std::array::from_fn(|i| (s.features[i] - mean[i]) * scale[i]))
+        .collect();
+
+    let mut bias = 0.;
+    let mut weights = [0.; FEATURE_COUNT];
+    for epoch in 0..config.epochs {
+        let (bias_gradient, gradient) = standardized
+            .par_iter()
+            .zip(samples.par_iter())
+            .map(|(x, sample)| {
+                let z = bias + (0..FEATURE_COUNT).map(|i| weights[i] * x[i]).sum::<f64>();
+                let error = sigmoid(z) - sample.outcome;
+                (error, x.map(|v| error * v))
+            })
+            .reduce(
+                || (0., [0.; FEATURE_COUNT]),
+                |a, b| (a.0 + b.0, std::array::from_fn(|i| a.1[i] + b.1[i])),
+            );
+
+        bias -= config.learning_rate * bias_gradient / n;
+        for i in 0..FEATURE_COUNT {
+            weights[i] -= config.learning_rate * (gradient[i] / n + config.l2 * weights[i]);
+        }
+
+        if epoch % 500 == 0 {
+            info!(
+                "epoch {}: log loss {:.4}",
+                epoch,
+                log_loss(samples, bias, &weights, &mean, &scale)
+            );
+        }
+    }
+
+    let raw: [f64; FEATURE_COUNT] = std::array::from_fn(|i| weights[i] * scale[i]);
+    LinearWeights {
+        bias: bias - (0..FEATURE_COUNT).map(|i| raw[i] * mean[i]).sum::<f64>(),
+        weights: FEATURE_NAMES
+            .iter()
+            .zip(raw)
+            .map(|(name, weight)| (name.to_string(), weight))
+            .collect(),
+    }
+}
+
+fn log_loss(
+    samples: &[Sample],
+    bias: f64,
+    weights: &[f64; FEATURE_COUNT],
+    mean: &[f64; FEATURE_COUNT],
+    scale: &[f64; FEATURE_COUNT],
+) -> f64 {
+    samples
+        .iter()
+        .map(|s| {
+            let z = bias
+                + (0..FEATURE_COUNT)
+                    .map(|i| weights[i] * (s.features[i] - mean[i]) * scale[i])
+                    .sum::<f64>();
+            let p = sigmoid(z).clamp(1e-9, 1. - 1e-9);
+            -(s.outcome * p.ln() + (1. - s.outcome) * (1. - p).ln())
+        })
+        .sum::<f64>()
+        / samples.len() as f64
+}
+
+fn write_samples(path: &str, samples: &[Sample]) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("failed to create {}", path))?;
+    let mut writer = BufWriter::new(file);
+    for sample in samples {
+        serde_json::to_writer(&mut writer, sample)?;
+        writeln!(writer)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn run(config: &SelfPlayConfig, search: &SearchConfig) -> Result<()> {
+    info!(
+        "playing {} self-play games, {:?} against {:?}",
+        config.games, config.policies[0], config.policies[1]
+    );
+
+    let games: Vec<Vec<Sample>> = (0..config.games)
+        .into_par_iter()
+        .map(|i| play_game(config, search, config.seed.wrapping_add(i)))
+        .collect();
+    let decided = games.iter().filter(|g| !g.is_empty()).count();
+    let samples: Vec<Sample> = games.into_iter().flatten().collect();
+    info!(
+        "{}/{} games gave a result, {} samples",
+        decided,
+        config.games,
+        samples.len()
+    );
+
+    write_samples(&config.samples_path, &samples)?;
+    if samples.is_empty() {
+        anyhow::bail!("no samples to fit, try a higher max_turns");
+    }
+
+    let weights = fit(&samples, config);
+    info!("fitted weights: {:?}", weights);
+
+    std::fs::write(&config.output_path, serde_json::to_string_pretty(&weights)?)
+        .with_context(|| format!("failed to write {}", config.output_path))?;
+    info!("wrote linear evaluator weights to {}", config.output_path);
+    Ok(())
+}
@@ -10,8 +10,10 @@ use serde_json::{json, Value};
 use std::hash::Hash;
 
 use crate::{
+    config::ScoreWeights,
     constants::MAX_TURNS,
     enemy::EnemyMove,
+    path::PathCache,
     utils::{get_neighbors, manhattan_distance},
 };
 pub type PlayerId = u8;
@@ -152,7 +154,7 @@ impl Tile {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, Debug, Hash)]
+#[derive(Clone, PartialEq, Eq, Debug, Hash, Serialize)]
 pub struct MoveCommand {
     pub from: Location,
     pub to: Location,
@@ -172,6 +174,23 @@ impl SerializedMoveCommand {
     }
 }
 
+// every term of get_score, already multiplied by its weight, punishments are negative
+// the terms sum up to the score
+#[derive(Clone, Copy, PartialEq, Debug, Default, Serialize)]
+pub struct ScoreBreakdown {
+    pub winner: f64,
+    pub army: f64,
+    pub fog: f64,
+    pub land: f64,
+    pub general_revealed: f64,
+    pub army_distance: f64,
+}
+impl ScoreBreakdown {
+    pub fn total(&self) -> f64 {
+        self.winner + self.army + self.fog + self.land + self.general_revealed + self.army_distance
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
 pub enum GeneralLocation {
     Known(Location),
@@ -202,6 +221,7 @@ pub struct GameState<const PLAYER_COUNT: usize, const W: usize, const H: usize>
     pub city_count: [u16; PLAYER_COUNT],
     pub general_revealed_to: [bool; PLAYER_COUNT],
     generals: [GeneralLocation; PLAYER_COUNT],
+    pub(crate) paths: PathCache<W, H>,
 }
 impl<const PLAYER_COUNT: usize, const W: usize, const H: usize> GameState<PLAYER_COUNT, W, H> {
     pub fn new(player_id: PlayerId, own_general: Location) -> Self {
@@ -216,6 +236,7 @@ impl<const PLAYER_COUNT: usize, const W: usize, const H: usize> GameState<PLAYER
             city_count: [1; PLAYER_COUNT],
             general_revealed_to: [false; PLAYER_COUNT],
             generals: [GeneralLocation::Unknown; PLAYER_COUNT],
+            paths: PathCache::default(),
         };
 
         state.generals[player_id as usize] = GeneralLocation::Known(own_general);
@@ -231,6 +252,12 @@ impl<const PLAYER_COUNT: usize, const W: usize, const H: usize> GameState<PLAYER
         self.generals[self.player_id as usize].unwrap_location()
     }
 
+    // the tiles next to location, on this board
+    #[inline]
+    pub fn neighbors(&self, location: Location) -> Vec<Location> {
+        get_neighbors(location, W, H)
+    }
+
     #[inline]
     pub fn get_tile(&self, location: Location) -> &Tile {
         &self.tiles[location.0][location.1]
@@ -238,10 +265,24 @@ impl<const PLAYER_COUNT: usize, const W: usize, const H: usize> GameState<PLAYER
     #[inline]
     pub fn update_tile(&self, location: Location, tile: Tile) -> Self {
         let mut new_state = self.clone();
-        new_state.tiles[location.0][location.1] = tile;
+        new_state.set_tile(location, tile);
         new_state
     }
 
+    // in place version of update_tile, for code that builds a state up tile by tile
+    pub(crate) fn set_tile(&mut self, location: Location, tile: Tile) {
+        if self.tiles[location.0][location.1].tile_type.occupiable() != tile.tile_type.occupiable()
+        {
+            // terrain changed, cached distances are no longer valid
+            self.paths = PathCache::default();
+        }
+        self.tiles[location.0][location.1] = tile;
+    }
+
+    pub(crate) fn set_general(&mut self, player_id: PlayerId, general: GeneralLocation) {
+        self.generals[player_id as usize] = general;
+    }
+
     pub fn change_tile_ownership(
         &self,
         location: Location,
@@ -524,7 +565,11 @@ impl<const PLAYER_COUNT: usize, const W: usize, const H: usize> GameState<PLAYER
         &self.generals
     }
 
-    pub fn get_score(&self, turn: &PlayerId) -> f64 {
+    pub fn get_score(&self, turn: &PlayerId, weights: &ScoreWeights) -> f64 {
+        self.get_score_breakdown(turn, weights).total()
+    }
+
+    pub fn get_score_breakdown(&self, turn: &PlayerId, weights: &ScoreWeights) -> ScoreBreakdown {
         let mut winner_reward = 0.5;
         if let Some(winner) = self.get_winner() {
             if winner == *turn {
@@ -534,7 +579,10 @@ impl<const PLAYER_COUNT: usize, const W: usize, const H: usize> GameState<PLAYER
             }
         }
         if *turn != self.player_id {
-            return winner_reward * 50.;
+            return ScoreBreakdown {
+                winner: winner_reward * weights.winner,
+                ..Default::default()
+            };
         }
         // reward is army / sum_all_armies
         let sum_all_armies: u64 = self.armies.iter().map(|a| *a as u64).sum();
@@ -564,11 +612,13 @@ impl<const PLAYER_COUNT: usize, const W: usize, const H: usize> GameState<PLAYER
         // having big enemy army with low manhattan distance from general is bad
         let mut army_distance_reward = 1;
         let mut army_distance_punishment = 1;
+        let general_distances = self.general_distances();
         for x in 0..W {
             for y in 0..H {
                 let tile: &Tile = self.get_tile((x, y));
 
-                let distance = manhattan_distance((x, y), self.get_own_general());
+                // real path distance, tiles walled off from the general are never a threat
+                let distance = general_distances.get((x, y)).map_or(u64::MAX, |d| d as u64);
                 if tile.owner == Some(*turn) && tile.population > 3 && distance > 4 {
                     // army_distance_reward += (distance).pow(2) * tile.population as u64;
                 }
@@ -645,13 +695,17 @@ impl<const PLAYER_COUNT: usize, const W: usize, const H: usize> GameState<PLAYER
             winner_reward, army_reward, fog_reward, land_reward, general_reward, general_revealed_punishment, army_distance_reward, army_distance_punishment
         );
 
-        winner_reward * 50. + army_reward * 20. + fog_reward * 5.
-            - general_revealed_punishment * 10.
-            + land_reward * 9.
-            // + standing_army_reward * 1.
-            // + army_distance_reward * 4.
-            - army_distance_punishment * 3.
-        // + general_army_reward * 3.
+        ScoreBreakdown {
+            winner: winner_reward * weights.winner,
+            army: army_reward * weights.army,
+            fog: fog_reward * weights.fog,
+            land: land_reward * weights.land,
+            general_revealed: -general_revealed_punishment * weights.general_revealed,
+            army_distance: -army_distance_punishment * weights.army_distance,
+            // standing_army: standing_army_reward * 1.
+            // army_distance_reward * 4.
+            // general_army_reward * 3.
+        }
     }
 
     pub fn get_hash(&self) -> u64 {
@@ -0,0 +1,287 @@
+// strategic layer above the search: every few turns pick what we are trying to achieve, the search
+// then looks at moves towards it first and gets a bonus in the evaluation for getting closer to it
+
+use crate::{
+    config::SearchConfig,
+    inference::{self, GeneralBelief},
+    path,
+    state::{GeneralLocation, GeneralsGameState, Location, MoveCommand, TileType},
+};
+
+// an enemy army this close to our general (in moves) with half its army is worth turning around for
+const THREAT_DISTANCE: u32 = 6;
+// cities further than this from our biggest army are not worth the walk
+const CITY_DISTANCE: u32 = 12;
+// army further than this from the target doesn't count as getting closer to it
+const REACH: u32 = 25;
+// before this the opening is still claiming land, not worth sending anyone looking
+const SCOUT_AFTER: u64 = 100;
+// an inferred general this likely is attacked as if we had seen it
+const ATTACK_CONFIDENCE: f64 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Goal {
+    // claim as much land as possible
+    Expand,
+    // pile our army up on a tile close to the enemy general before attacking
+    Gather { staging: Location },
+    Attack { general: Location },
+    TakeCity { city: Location },
+    // an enemy army is close to our general, bring ours home until it's gone
+    Defend,
+    // walk towards where the enemy general most likely is until we can see it
+    Scout { target: Location },
+}
+
+// the goal and how much it counts next to the evaluator's score
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Plan {
+    pub goal: Goal,
+    pub weight: f64,
+}
+
+impl Plan {
+    // evaluation bonus for how far the goal got, weight is the fraction of the evaluator's
+    // score range a finished goal is worth
+    pub fn bonus(&self, state: &GeneralsGameState, score_range: f64) -> f64 {
+        self.weight * score_range * progress(&self.goal, state)
+    }
+
+    // how strongly the search should look at a move first, on top of its capture priority
+    pub fn move_bias(&self, state: &GeneralsGameState, m: &MoveCommand) -> u32 {
+        let towards = |target: Location| {
+            let distances = state.terrain_distances_to(target);
+            distances.get_or_unreachable(m.to) < distances.get_or_unreachable(m.from)
+        };
+        let helps = match self.goal {
+            Goal::Expand => claimable(state, m.to),
+            Goal::Gather { staging } => towards(staging),
+            Goal::Attack { general } => towards(general),
+            Goal::TakeCity { city } => towards(city),
+            Goal::Defend => towards(state.get_own_general()),
+            Goal::Scout { target } => towards(target),
+        };
+        if helps {
+            3
+        } else {
+            0
+        }
+    }
+}
+
+fn claimable(state: &GeneralsGameState, location: Location) -> bool {
+    let tile = state.get_tile(location);
+    tile.owner.is_none()
+        && matches!(
+            tile.tile_type,
+            TileType::VisibleEmpty | TileType::AssumedEmpty
+        )
+}
+
+// 0 to 1, 1 once the goal is done
+fn progress(goal: &Goal, state: &GeneralsGameState) -> f64 {
+    let me = state.player_id();
+    let owned = |location: Location| state.get_tile(location).owner == Some(me);
+    match *goal {
+        Goal::Expand => {
+            let lands = state.lands[me as usize] as f64;
+            lands / (lands + state.lands[1 - me as usize] as f64).max(1.)
+        }
+        Goal::Gather { staging } => closeness(state, staging),
+        Goal::Attack { general } if owned(general) => 1.,
+        Goal::Attack { general } => closeness(state, general),
+        Goal::TakeCity { city } if owned(city) => 1.,
+        Goal::TakeCity { city } => closeness(state, city),
+        Goal::Defend => closeness(state, state.get_own_general()),
+        Goal::Scout { target } if state.fog_mask[target.0][target.1] != 0 => 1.,
+        Goal::Scout { target } => closeness(state, target),
+    }
+}
+
+// share of our army close to target, weighted by how close it is
+fn closeness(state: &GeneralsGameState, target: Location) -> f64 {
+    let me = state.player_id();
+    let distances = state.terrain_distances_to(target);
+    let mut weighted = 0.;
+    for x in 0..25 {
+        for y in 0..25 {
+            let tile = state.get_tile((x, y));
+            if tile.owner != Some(me) || tile.population < 2 {
+                continue;
+            }
+            if let Some(distance) = distances.get((x, y)) {
+                let near = 1. - distance.min(REACH) as f64 / REACH as f64;
+                weighted += (tile.population - 1) as f64 * near;
+            }
+        }
+    }
+    weighted / (state.armies[me as usize] as f64).max(1.)
+}
+
+fn biggest_army(state: &GeneralsGameState) -> Option<(Location, u16)> {
+    let me = Some(state.player_id());
+    let mut biggest = None;
+    for x in 0..25 {
+        for y in 0..25 {
+            let tile = state.get_tile((x, y));
+            if tile.owner == me && biggest.is_none_or(|(_, army)| tile.population > army) {
+                biggest = Some(((x, y), tile.population));
+            }
+        }
+    }
+    biggest
+}
+
+// what to go for in this position, the first that applies of: defend, attack or gather for it,
+// take a city, scout for the general, expand
+pub fn choose(state: &GeneralsGameState, belief: &GeneralBelief) -> Goal {
+    let me = state.player_id();
+    let general = state.get_own_general();
+    let general_army = state.get_tile(general).population;
+
+    let general_distances = state.general_distances();
+    let threatened = state.tiles().iter().enumerate().any(|(x, column)| {
+        column.iter().enumerate().any(|(y, tile)| {
+            tile.tile_type.is_enemy()
+                && tile.population >= 2
+                && tile.population * 2 >= general_army
+                && general_distances
+                    .get((x, y))
+                    .is_some_and(|d| d <= THREAT_DISTANCE)
+        })
+    });
+    if threatened {
+        return Goal::Defend;
+    }
+
+    let Some((army_location, army)) = biggest_army(state) else {
+        return Goal::Expand;
+    };
+
+    let enemy_general = match state.generals()[1 - me as usize] {
+        GeneralLocation::Known(general) => Some(general),
+        GeneralLocation::Unknown => belief
+            .top(1)
+            .into_iter()
+            .find(|(_, p)| *p >= ATTACK_CONFIDENCE)
+            .map(|(general, _)| general),
+        GeneralLocation::Dead(_) => None,
+    };
+    if let Some(enemy_general) = enemy_general {
+        let distances = state.terrain_distances_to(enemy_general);
+        if let Some(cost) = path::dijkstra_to(state, enemy_general, me).get(army_location) {
+            // the way there costs a move and whatever defends each tile, the general itself holds
+            // what we see on it or what we guess it holds while it's fogged
+            let needed = cost - state.get_tile(enemy_general).population as u32
+                + inference::general_army(state, enemy_general)
+                + 1;
+            if army as u32 > needed {
+                return Goal::Attack {
+                    general: enemy_general,
+                };
+            }
+
+            // our tile closest to their general, armies meet there
+            let staging = (0..25)
+                .flat_map(|x| (0..25).map(move |y| (x, y)))
+                .filter(|l| state.get_tile(*l).owner == Some(me))
+                .min_by_key(|l| distances.get_or_unreachable(*l))
+                .unwrap_or(general);
+            return Goal::Gather { staging };
+        }
+    }
+
+    let army_distances = state.terrain_distances_to(army_location);
+    let city = (0..25)
+        .flat_map(|x| (0..25).map(move |y| (x, y)))
+        .filter(|l| state.get_tile(*l).tile_type == TileType::VisibleNeutralCity)
+        .filter_map(|l| army_distances.get(l).map(|d| (l, d)))
+        .filter(|(l, d)| {
+            *d <= CITY_DISTANCE && army as u32 > state.get_tile(*l).population as u32 + d + 1
+        })
+        .min_by_key(|(_, d)| *d);
+    if let Some((city, _)) = city {
+        return Goal::TakeCity { city };
+    }
+
+    if state.generals()[1 - me as usize] == GeneralLocation::Unknown && state.turn >= SCOUT_AFTER {
+        if let Some((target, _)) = belief.top(1).first() {
+            return Goal::Scout { target: *target };
+        }
+    }
+
+    Goal::Expand
+}
+
+#[derive(Debug)]
+pub struct Planner {
+    plan: Plan,
+    decided_at: u64,
+    replan_every: u64,
+    // turn and goal of every change this game, the first goal included
+    transitions: Vec<(u64, Goal)>,
+}
+
+impl Planner {
+    pub fn new(state: &GeneralsGameState, config: &SearchConfig, belief: &GeneralBelief) -> Self {
+        let goal = choose(state, belief);
+        info!(turn = state.turn, goal = ?goal, "first goal");
+        Self {
+            plan: Plan {
+                goal,
+                weight: config.goal_weight,
+            },
+            decided_at: state.turn,
+            replan_every: config.replan_every,
+            transitions: vec![(state.turn, goal)],
+        }
+    }
+
+    pub fn plan(&self) -> Plan {
+        self.plan
+    }
+
+    pub fn transitions(&self) -> &[(u64, Goal)] {
+        &self.transitions
+    }
+
+    // picks the goal again when it's time, when it's done or when it can't be reached anymore,
+    // a threat switches to defending right away, true if the goal changed
+    pub fn update(&mut self, state: &GeneralsGameState, belief: &GeneralBelief) -> bool {
+        let goal = choose(state, belief);
+        let due = state.turn >= self.decided_at + self.replan_every
+            || progress(&self.plan.goal, state) >= 1.
+            || stale(&self.plan.goal, state, belief)
+            || (goal == Goal::Defend && self.plan.goal != Goal::Defend);
+        if !due || goal == self.plan.goal {
+            return false;
+        }
+
+        info!(
+            turn = state.turn,
+            from = ?self.plan.goal,
+            to = ?goal,
+            "goal transition"
+        );
+        self.plan.goal = goal;
+        self.decided_at = state.turn;
+        self.transitions.push((state.turn, goal));
+        true
+    }
+}
+
+// the target went away under the goal, e.g. the city was taken by someone else
+// or what we inferred about the enemy general changed
+fn stale(goal: &Goal, state: &GeneralsGameState, belief: &GeneralBelief) -> bool {
+    let me = state.player_id();
+    match *goal {
+        Goal::Expand | Goal::Defend => false,
+        Goal::Gather { staging } => state.get_tile(staging).owner != Some(me),
+        Goal::Attack { general } => belief.probability(general) < ATTACK_CONFIDENCE,
+        Goal::Scout { target } => belief.probability(target) == 0.,
+        Goal::TakeCity { city } => {
+            let tile = state.get_tile(city);
+            tile.owner != Some(me) && tile.tile_type != TileType::VisibleNeutralCity
+        }
+    }
+}
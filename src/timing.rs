@@ -0,0 +1,121 @@
+// decides how long we can search each turn
+// the server ticks at a fixed cadence, whatever we don't leave for the network and our own bookkeeping can go to the search
+
+use std::time::Duration;
+
+use crate::{config::SearchConfig, state::GeneralsGameState, utils::get_neighbors};
+
+// weight of the newest observation in the moving averages
+const SMOOTHING: f64 = 0.2;
+
+// an enemy army this close to our general (in moves) means we have to think harder
+const THREAT_DISTANCE: u32 = 6;
+
+#[derive(Debug, Default)]
+pub struct TimeManager {
+    // moving average of the time between two game updates
+    tick_interval: Option<f64>,
+    // moving average of how far a single interval strays from the average, covers network hiccups
+    jitter: f64,
+    // moving average of the time we spend on a turn outside of the search
+    overhead: f64,
+}
+
+impl TimeManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn observe_update(&mut self, time_between_updates: Duration) {
+        let interval = time_between_updates.as_secs_f64();
+        match self.tick_interval {
+            Some(average) => {
+                self.jitter = smooth(self.jitter, (interval - average).abs());
+                self.tick_interval = Some(smooth(average, interval));
+            }
+            None => self.tick_interval = Some(interval),
+        }
+    }
+
+    // time between receiving an update and sending our move, minus the search itself
+    pub fn observe_overhead(&mut self, overhead: Duration) {
+        self.overhead = smooth(self.overhead, overhead.as_secs_f64());
+    }
+
+    pub fn budget(&self, state: &GeneralsGameState, config: &SearchConfig) -> Duration {
+        let tactical = is_tactical(state);
+
+        let budget = match self.tick_interval {
+            Some(interval) if config.adaptive_time => {
+                let margin = config.latency_margin_ms as f64 / 1000. + 2. * self.jitter;
+                let available = (interval - self.overhead - margin).max(0.);
+                let fraction = if tactical {
+                    config.tactical_time_fraction
+                } else {
+                    config.time_fraction
+                };
+                Duration::from_secs_f64(available * fraction)
+            }
+            // nothing observed yet, fall back to the fixed thinking time
+            _ => config.thinking_time(),
+        };
+
+        // configs built in code skip validation, a max below the min shouldn't panic the clamp
+        let min = config.min_thinking_time_ms.min(config.max_thinking_time_ms);
+        let budget = budget.clamp(
+            Duration::from_millis(min),
+            Duration::from_millis(config.max_thinking_time_ms),
+        );
+
+        debug!(
+            "time budget: {:?}, tactical: {}, tick interval: {:?}, jitter: {}, overhead: {}",
+            budget, tactical, self.tick_interval, self.jitter, self.overhead
+        );
+
+        budget
+    }
+}
+
+fn smooth(average: f64, value: f64) -> f64 {
+    average * (1. - SMOOTHING) + value * SMOOTHING
+}
+
+// our general was seen, or an enemy army is next to our land or close to our general
+pub fn is_tactical(state: &GeneralsGameState) -> bool {
+    let player_id = state.player_id();
+    if state
+        .general_revealed_to
+        .iter()
+        .enumerate()
+        .any(|(player, revealed)| player != player_id as usize && *revealed)
+    {
+        return true;
+    }
+
+    let general_distances = state.general_distances();
+    for x in 0..25 {
+        for y in 0..25 {
+            let tile = state.get_tile((x, y));
+            if !tile.tile_type.is_enemy() || tile.population < 2 {
+                continue;
+            }
+
+            if general_distances
+                .get((x, y))
+                .is_some_and(|d| d <= THREAT_DISTANCE)
+            {
+                return true;
+            }
+
+            let threatens_land = get_neighbors((x, y), 25, 25).into_iter().any(|n| {
+                let neighbor = state.get_tile(n);
+                neighbor.owner == Some(player_id) && neighbor.population < tile.population
+            });
+            if threatens_land {
+                return true;
+            }
+        }
+    }
+
+    false
+}
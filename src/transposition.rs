@@ -0,0 +1,139 @@
+// statistics shared between tree nodes that reach the same position through different move orders
+// fixed size, so memory stays bounded no matter how long we search
+
+use std::{
+    cell::RefCell,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+};
+
+const SHARD_COUNT: usize = 64;
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Entry {
+    pub hash: u64,
+    pub visits: u32,
+    pub sum_rewards: f64,
+    generation: u32,
+}
+
+pub struct TranspositionTable {
+    shards: Vec<Mutex<Vec<Option<Entry>>>>,
+    slots_per_shard: usize,
+    // bumped for every new search root, entries from older searches are ignored and replaced first
+    generation: AtomicU32,
+}
+
+impl TranspositionTable {
+    pub fn with_memory_budget(megabytes: usize) -> Self {
+        let slots = megabytes * 1024 * 1024 / std::mem::size_of::<Option<Entry>>();
+        let slots_per_shard = (slots / SHARD_COUNT).max(1);
+
+        Self {
+            shards: (0..SHARD_COUNT)
+                .map(|_| Mutex::new(vec![None; slots_per_shard]))
+                .collect(),
+            slots_per_shard,
+            generation: AtomicU32::new(0),
+        }
+    }
+
+    pub fn new_search(&self) {
+        self.generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    #[inline]
+    fn locate(&self, hash: u64) -> (usize, usize) {
+        let shard = hash as usize % SHARD_COUNT;
+        let slot = (hash as usize / SHARD_COUNT) % self.slots_per_shard;
+        (shard, slot)
+    }
+
+    // only what the current search recorded, the older searches were made from other roots
+    pub fn get(&self, hash: u64) -> Option<Entry> {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let (shard, slot) = self.locate(hash);
+        let shard = self.shards[shard].lock().unwrap();
+        shard[slot].filter(|entry| entry.hash == hash && entry.generation == generation)
+    }
+
+    pub fn record(&self, hash: u64, reward: f64) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        let (shard, slot) = self.locate(hash);
+        let mut shard = self.shards[shard].lock().unwrap();
+
+        match &mut shard[slot] {
+            Some(entry) if entry.hash == hash && entry.generation == generation => {
+                entry.visits += 1;
+                entry.sum_rewards += reward;
+            }
+            // replace empty slots, leftovers from older searches and barely visited positions
+            slot @ None => *slot = Some(Entry::fresh(hash, reward, generation)),
+            Some(entry) if entry.generation != generation || entry.visits <= 1 => {
+                *entry = Entry::fresh(hash, reward, generation)
+            }
+            // keep the well explored position, this visit just isn't shared
+            Some(_) => {}
+        }
+    }
+}
+
+impl Entry {
+    fn fresh(hash: u64, reward: f64, generation: u32) -> Self {
+        Self {
+            hash,
+            visits: 1,
+            sum_rewards: reward,
+            generation,
+        }
+    }
+}
+
+thread_local! {
+    // the table search workers on this thread record into, backprop has no other way to reach it
+    static ACTIVE: RefCell<Option<Arc<TranspositionTable>>> = const { RefCell::new(None) };
+}
+
+pub struct Binding;
+
+impl Drop for Binding {
+    fn drop(&mut self) {
+        ACTIVE.with(|active| *active.borrow_mut() = None);
+    }
+}
+
+// makes `table` the one backprop records into on this thread until the binding is dropped
+pub fn bind(table: Option<Arc<TranspositionTable>>) -> Binding {
+    ACTIVE.with(|active| *active.borrow_mut() = table);
+    Binding
+}
+
+pub fn record(hash: u64, reward: f64) {
+    ACTIVE.with(|active| {
+        if let Some(table) = active.borrow().as_ref() {
+            table.record(hash, reward);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_search_starts_over() {
+        let table = TranspositionTable::with_memory_budget(1);
+        table.record(42, 1.);
+        table.record(42, 3.);
+        let entry = table.get(42).unwrap();
+        assert_eq!((entry.visits, entry.sum_rewards), (2, 4.));
+
+        table.new_search();
+        assert!(table.get(42).is_none());
+        table.record(42, 5.);
+        let entry = table.get(42).unwrap();
+        assert_eq!((entry.visits, entry.sum_rewards), (1, 5.));
+    }
+}